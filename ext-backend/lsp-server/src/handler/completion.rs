@@ -1,5 +1,6 @@
+use crate::position::byte_column;
 use crate::state::SharedServerState;
-use crate::symbol_info::symbol;
+use crate::symbol_info::symbol::SpiceSymbolKind;
 use crate::symbol_info::table::SymbolTable;
 use spice_parser_core::ast::component::ComponentPartial;
 use spice_parser_core::ast::Atom;
@@ -29,10 +30,10 @@ pub async fn on_completion(
         .await;
 
     // 2. 获取文档内容
-    let (maybe_source, existed) = {
+    let (maybe_source, existed, snippet_support) = {
         let s = state.lock().await;
         let opt = s.documents.get(&uri).cloned();
-        (opt, s.documents.contains_key(&uri))
+        (opt, s.documents.contains_key(&uri), s.client_support.snippet)
     };
 
     //let symbol = maybe_source.unwrap().symbols.unwrap();
@@ -142,28 +143,90 @@ pub async fn on_completion(
         )
         .await;
 
-        for name in cmp_name{
+        for (index, name) in cmp_name.into_iter().enumerate() {
+            let rank = rank_for(&name, line_text, CompletionRank::Fallback);
             let cmp_item = CompletionItem {
                 label: name.clone(),
                 kind: Some(CompletionItemKind::TEXT),
                 detail: Some("SPICE Component".to_string()),
-                documentation: Some(Documentation::String("SPICE电路元件".to_string())),
                 insert_text: Some(name),
                 insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
                 ..Default::default()
             };
-            items.push(cmp_item);
+            items.push(ranked_item(cmp_item, rank, index));
         }
         
-    }else if line_text.len() >= 3 && col == 3 {
-        if let Some((label, snippet)) = generate_snippet(line_text, source.symbols.as_ref()) {
-            items.push(CompletionItem {
-                label: label.to_string(),
-                kind: Some(CompletionItemKind::SNIPPET),
-                insert_text: Some(snippet),
-                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                ..Default::default()
-            })
+    }else if line_text.len() >= 3 && col == 3 && snippet_support {
+        if let Some(snippet_item) = snippet_item(line_text, source.symbols.as_ref(), safe_line) {
+            items.push(ranked_item(snippet_item, CompletionRank::Circuit, 0));
+        }
+    } else {
+        let prefix = cursor_prefix(line_text, col as u32);
+        if let Some((sections, typed)) = section_keyword_completions(prefix) {
+            for (index, keyword) in sections.into_iter().enumerate() {
+                let rank = rank_for(keyword, typed, CompletionRank::Circuit);
+                let section_item = CompletionItem {
+                    label: keyword.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some("SPICE Section".to_string()),
+                    insert_text: Some(format!("{} ", keyword)),
+                    insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    filter_text: Some(keyword.to_string()),
+                    ..Default::default()
+                };
+                items.push(ranked_item(section_item, rank, index));
+            }
+        } else if let Some((field, typed)) = output_completions(prefix) {
+            let output_items = match field {
+                OutputField::Source => output_source_items(),
+                OutputField::Variable => {
+                    output_variable_items(source.symbols.as_ref(), safe_line, snippet_support)
+                }
+            };
+            for (index, item) in output_items.into_iter().enumerate() {
+                let rank = rank_for(&item.label, typed, CompletionRank::Circuit);
+                items.push(ranked_item(item, rank, index));
+            }
+        } else if let Some((field, candidates, typed)) = model_completions(prefix) {
+            for (index, candidate) in candidates.into_iter().enumerate() {
+                let rank = rank_for(candidate, typed, CompletionRank::Fallback);
+                let (kind, detail, insert_text) = match field {
+                    ModelField::Type => (
+                        CompletionItemKind::ENUM_MEMBER,
+                        "SPICE Model Type".to_string(),
+                        format!("{} ", candidate),
+                    ),
+                    ModelField::Param(model_type) => (
+                        CompletionItemKind::PROPERTY,
+                        format!("{} model parameter", model_type),
+                        format!("{}=", candidate),
+                    ),
+                };
+                let model_item = CompletionItem {
+                    label: candidate.to_string(),
+                    kind: Some(kind),
+                    detail: Some(detail),
+                    insert_text: Some(insert_text),
+                    insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    filter_text: Some(candidate.to_string()),
+                    ..Default::default()
+                };
+                items.push(ranked_item(model_item, rank, index));
+            }
+        } else if let Some((detail, candidates)) =
+            partial_parse_line(prefix).and_then(|p| next_field_completions(&p, source.symbols.as_ref(), safe_line))
+        {
+            for (index, (candidate, rank)) in candidates.into_iter().enumerate() {
+                let field_item = CompletionItem {
+                    label: candidate.clone(),
+                    kind: Some(CompletionItemKind::VALUE),
+                    detail: Some(detail.clone()),
+                    insert_text: Some(candidate),
+                    insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    ..Default::default()
+                };
+                items.push(ranked_item(field_item, rank, index));
+            }
         }
     }
 
@@ -173,177 +236,583 @@ pub async fn on_completion(
 }
 
 
-fn generate_snippet(str:&str,symbols: Option<&SymbolTable>,) -> Option<(String, String)> {
+/// completionItem/resolve：按 label 延迟填充 detail 与文档，避免首次补全列表过重
+pub async fn on_completion_resolve(
+    state: SharedServerState,
+    item: CompletionItem,
+) -> Result<CompletionItem, tower_lsp::jsonrpc::Error> {
+    let markdown = state.lock().await.client_support.markdown;
+    Ok(resolve_item(item, markdown))
+}
 
-    let names: Vec<String> = symbols
-                            .map(|s| s.get_node_names())
-                            .unwrap_or_default();
+fn resolve_item(mut item: CompletionItem, markdown: bool) -> CompletionItem {
+    if item.documentation.is_some() {
+        return item;
+    }
+    let Some((summary, doc)) = documentation_key(&item).and_then(item_documentation) else {
+        return item;
+    };
+    if item.detail.is_none() {
+        item.detail = Some(summary.to_string());
+    }
+    item.documentation = Some(if markdown {
+        Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: doc.to_string(),
+        })
+    } else {
+        Documentation::String(doc.to_string())
+    });
+    item
+}
+
+/// 查文档用的名称：模板片段的 label 以中文说明开头，元件字母放在 `data` 中；
+/// 元件名补全项（TEXT）与 `.` 开头的命令按 label 查。
+/// 节点、分节关键字、分析类型、模型参数等不能按首字母套用元件文档
+fn documentation_key(item: &CompletionItem) -> Option<&str> {
+    if let Some(key) = item.data.as_ref().and_then(|data| data.as_str()) {
+        return Some(key);
+    }
+    (item.label.trim_start().starts_with('.') || item.kind == Some(CompletionItemKind::TEXT))
+        .then_some(item.label.as_str())
+}
+
+/// 元件（按首字母，如 `R`、`R1`）与命令（如 `.TRAN`）的说明文档
+fn item_documentation(label: &str) -> Option<(&'static str, &'static str)> {
+    let label = label.trim().to_uppercase();
+    if label.starts_with('.') {
+        return command_documentation(&label);
+    }
+    match label.chars().next()? {
+        'B' => Some(("砷化镓 MES 场效应晶体管", "```spice\nB<name> <drain node> <gate node> <source node> <model name> [area value]\n```")),
+        'C' => Some(("电容", "```spice\nC<name> <(+) node> <(-) node> [model name] <value> [IC=<initial value>]\n```\n\n例：`C1 1 0 1uF IC=0`")),
+        'D' => Some(("二极管", "```spice\nD<name> <(+) node> <(-) node> <model name> [area value]\n```\n\n例：`D1 2 3 D1N914`")),
+        'E' => Some(("电压控制电压源", "```spice\nE<name> <(+) node> <(-) node> <(+) controlling node> <(-) controlling node> <gain>\n```")),
+        'F' => Some(("电流控制电流源", "```spice\nF<name> <(+) node> <(-) node> <controlling V device name> <gain>\n```")),
+        'G' => Some(("电压控制电流源", "```spice\nG<name> <(+) node> <(-) node> <(+) controlling node> <(-) controlling node> <transconductance>\n```")),
+        'H' => Some(("电流控制电压源", "```spice\nH<name> <(+) node> <(-) node> <controlling V device name> <transresistance>\n```")),
+        'I' => Some(("独立电流源", "```spice\nI<name> <(+) node> <(-) node> [[DC] <value>] [AC <magnitude> [phase]] [transient]\n```")),
+        'J' => Some(("结型场效应晶体管", "```spice\nJ<name> <drain node> <gate node> <source node> <model name> [area value]\n```")),
+        'K' => Some(("互感（电感耦合）", "```spice\nK<name> L<inductor name> <L<inductor name>>* <coupling value>\n```\n\n例：`K1 L1 L2 0.99`")),
+        'L' => Some(("电感", "```spice\nL<name> <(+) node> <(-) node> [model name] <value> [IC=<initial value>]\n```\n\n例：`L1 1 2 10uH IC=0`")),
+        'M' => Some(("MOS 场效应晶体管", "```spice\nM<name> <drain node> <gate node> <source node> <bulk/substrate node> <model name>\n+ [L=<value>] [W=<value>] [AD=<value>] [AS=<value>] [PD=<value>] [PS=<value>]\n+ [NRD=<value>] [NRS=<value>] [NRG=<value>] [NRB=<value>] [M=<value>] [N=<value>]\n```")),
+        'Q' => Some(("双极结型晶体管", "```spice\nQ<name> <collector node> <base node> <emitter node> [substrate node] <model name> [area value]\n```\n\n例：`Q1 C B E QNPN`")),
+        'R' => Some(("电阻", "```spice\nR<name> <(+) node> <(-) node> [model name] <value> [TC=<TC1>[,<TC2>]]\n```\n\n例：`R1 1 2 1k`")),
+        'S' => Some(("电压控制开关", "```spice\nS<name> <(+) switch node> <(-) switch node> <(+) controlling node> <(-) controlling node> <model name>\n```")),
+        'T' => Some(("传输线", "```spice\nT<name> <A port (+) node> <A port (-) node> <B port (+) node> <B port (-) node> [model name]\n+ Z0=<value> [TD=<value>] [F=<value> [NL=<value>]]\n```")),
+        'V' => Some(("独立电压源", "```spice\nV<name> <(+) node> <(-) node> [[DC] <value>] [AC <magnitude> [phase]] [transient]\n```\n\n例：`V1 1 0 DC 5`")),
+        'W' => Some(("电流控制开关", "```spice\nW<name> <(+) switch node> <(-) switch node> <controlling V device name> <model name>\n```")),
+        'X' => Some(("子电路调用", "```spice\nX<name> [node]* <subcircuit name> [PARAMS: <<name>=<value>>*] [TEXT: <<name>=<text value>>*]\n```")),
+        'Z' => Some(("IGBT", "```spice\nZ<name> <collector> <gate> <emitter> <model name>\n```")),
+        _ => None,
+    }
+}
+
+fn command_documentation(keyword: &str) -> Option<(&'static str, &'static str)> {
+    match keyword {
+        ".AC" => Some(("交流分析", "```spice\n.AC <LIN|OCT|DEC> <points value> <start frequency value> <end frequency value>\n```\n\n例：`.AC DEC 10 1Hz 1MegHz`")),
+        ".DC" => Some(("直流扫描分析", "```spice\n.DC [LIN|OCT|DEC] <sweep variable name> <start> <end> <increment>\n.DC <sweep variable name> LIST <value>*\n```")),
+        ".END" => Some(("网表结束", "```spice\n.END\n```")),
+        ".ENDS" => Some(("子电路定义结束", "```spice\n.ENDS [subcircuit name]\n```")),
+        ".FOUR" => Some(("傅里叶分析", "```spice\n.FOUR <frequency value> [no. harmonics value] <output variable>*\n```")),
+        ".FUNC" => Some(("自定义函数", "```spice\n.FUNC <name>([arg]*) {<body>}\n```\n\n例：`.FUNC DECAY(CNST) {E(-CNST*TIME)}`")),
+        ".GLOBAL" => Some(("全局节点", "```spice\n.GLOBAL <global node name>*\n```")),
+        ".IC" => Some(("瞬态初始条件", "```spice\n.IC <V(<node>[,<node>])=<value>>* <I(<inductor>)=<value>>*\n```")),
+        ".INC" => Some(("包含文件", "```spice\n.INC \"<file name>\"\n```")),
+        ".LIB" => Some(("引用库文件", "```spice\n.LIB [\"<file name>\"]\n```")),
+        ".MODEL" => Some(("模型定义", "```spice\n.MODEL <model name> [AKO: <reference model name>] <model type> ([<parameter name>=<value>]*)\n```\n\n例：`.MODEL QNPN NPN (IS=1e-16 BF=100)`")),
+        ".NODESET" => Some(("初始偏置点猜测", "```spice\n.NODESET <V(<node>[,<node>])=<value>>* <I(<inductor>)=<value>>*\n```")),
+        ".NOISE" => Some(("噪声分析", "```spice\n.NOISE V(<node>[,<node>]) <name> [interval value]\n```")),
+        ".OP" => Some(("偏置点分析", "```spice\n.OP\n```")),
+        ".OPTIONS" => Some(("仿真选项", "```spice\n.OPTIONS [<option name>]* [<option name>=<value>]*\n```\n\n例：`.OPTIONS RELTOL=.01 NOECHO`")),
+        ".PARAM" => Some(("全局参数", "```spice\n.PARAM <<name>=<value>>*\n```\n\n例：`.PARAM PI=3.14159, TWO_PI={2*3.14159}`")),
+        ".PLOT" => Some(("行式打印绘图", "```spice\n.PLOT <analysis type> <output variable>* [(<lower limit value>,<upper limit value>)]*\n```")),
+        ".PRINT" => Some(("打印输出", "```spice\n.PRINT[/DGTLCHG] <analysis type> <output variable>*\n```")),
+        ".PROBE" => Some(("Probe 输出", "```spice\n.PROBE[/CSDF] [output variable]*\n```")),
+        ".SENS" => Some(("灵敏度分析", "```spice\n.SENS <output variable>*\n```")),
+        ".STEP" => Some(("参数扫描", "```spice\n.STEP [LIN|OCT|DEC] <sweep variable name> <start> <end> <increment>\n.STEP <sweep variable name> LIST <value>*\n```")),
+        ".SUBCKT" => Some(("子电路定义", "```spice\n.SUBCKT <name> [node]* [OPTIONAL: <<interface node>=<default value>>*]\n+ [PARAMS: <<name>=<value>>*] [TEXT: <<name>=<text value>>*]\n```")),
+        ".TEMP" => Some(("仿真温度", "```spice\n.TEMP <temperature value>*\n```")),
+        ".TF" => Some(("小信号传递函数", "```spice\n.TF <output variable> <input source name>\n```")),
+        ".TRAN" => Some(("瞬态分析", "```spice\n.TRAN[/OP] <print step value> <final time value> [no-print value [step ceiling value]] [SKIPBP]\n```\n\n例：`.TRAN 1ns 100ns`")),
+        _ => None,
+    }
+}
+
+/// 补全项排序分级，数值越小越靠前
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CompletionRank {
+    /// 与当前已输入内容前缀匹配
+    ExactPrefix = 0,
+    /// 来自当前电路（符号表）的候选
+    Circuit = 1,
+    /// 通用兜底候选
+    Fallback = 2,
+}
+
+/// 无符号信息时使用的占位节点名
+const FALLBACK_NODES: [&str; 3] = ["N1", "N2", "N3"];
+
+/// 前缀匹配的候选提升为 `ExactPrefix`，否则保持给定的分级
+fn rank_for(label: &str, typed: &str, otherwise: CompletionRank) -> CompletionRank {
+    let typed = typed.trim();
+    if !typed.is_empty() && label.to_uppercase().starts_with(&typed.to_uppercase()) {
+        CompletionRank::ExactPrefix
+    } else {
+        otherwise
+    }
+}
+
+/// 设置 sort_text / filter_text：先按分级排序，同级内保持插入顺序
+///
+/// 未指定 filter_text 时按 label 过滤；insert_text 可能是带 `${1|...|}` 占位的片段，不能用于过滤。
+fn ranked_item(mut item: CompletionItem, rank: CompletionRank, index: usize) -> CompletionItem {
+    item.sort_text = Some(format!("{}_{:04}", rank as u8, index));
+    if item.filter_text.is_none() {
+        item.filter_text = Some(item.label.clone());
+    }
+    item
+}
+
+/// 节点候选：电路中已有的节点在前（光标所在子电路的引脚与节点优先），`N1,N2,N3` 兜底项在后（去重）
+fn node_candidates(symbols: Option<&SymbolTable>, line: usize) -> Vec<String> {
+    let mut names: Vec<String> = symbols
+        .map(|s| s.node_names_in_scope(line))
+        .unwrap_or_default();
+    for fallback in FALLBACK_NODES {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(fallback)) {
+            names.push(fallback.to_string());
+        }
+    }
+    names
+}
+
+/// 光标之前的行内文本；`col` 是 LSP 的 UTF-16 列，emoji 等占两个码元
+fn cursor_prefix(line_text: &str, col: u32) -> &str {
+    &line_text[..byte_column(line_text, col)]
+}
+
+/// `.SUBCKT` 行可用的分节关键字（按书写顺序）
+const SUBCKT_SECTIONS: [&str; 3] = ["OPTIONAL:", "PARAMS:", "TEXT:"];
+/// `X` 子电路调用行可用的分节关键字（按书写顺序）
+const X_SECTIONS: [&str; 2] = ["PARAMS:", "TEXT:"];
+
+/// 光标前文本已越过必需字段时，返回可补全的分节关键字及正在输入的部分
+///
+/// - `.SUBCKT <name> <pin>...` 至少需要名称和一个引脚
+/// - `X<name> <node>... <subckt name>` 至少需要一个节点和子电路名
+///
+/// 已出现的分节之前的关键字不再提供，例如写过 `PARAMS:` 后只剩 `TEXT:`。
+fn section_keyword_completions(prefix: &str) -> Option<(Vec<&'static str>, &str)> {
+    let mut tokens: Vec<&str> = prefix.split_whitespace().collect();
+    // 光标紧贴在单词后，最后一个 token 视为正在输入的关键字
+    let typed = if prefix.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        tokens.pop().unwrap_or("")
+    };
+    let first = tokens.first()?;
+
+    let sections: &[&'static str] = if first.eq_ignore_ascii_case(".SUBCKT") {
+        &SUBCKT_SECTIONS
+    } else if first.starts_with(['X', 'x']) {
+        &X_SECTIONS
+    } else {
+        return None;
+    };
+    if tokens.len() < 3 {
+        return None;
+    }
+
+    // 找到最后一个已写出的分节，只提供其后的关键字
+    let last_written = tokens
+        .iter()
+        .filter_map(|t| {
+            let word = t.trim_end_matches(':');
+            sections
+                .iter()
+                .position(|s| s.trim_end_matches(':').eq_ignore_ascii_case(word))
+        })
+        .max();
+    let start = last_written.map_or(0, |i| i + 1);
 
-     // ${2|n1,n2,n3|}
-    let node_choices_2 = if names.is_empty() {
-        "${2|N1,N2,N3|}".to_string()
+    let remaining = sections[start..].to_vec();
+    if remaining.is_empty() {
+        None
     } else {
-        format!("${{2|{}|}}", names.join(","))
+        Some((remaining, typed))
+    }
+}
+
+/// `.PRINT`/`.PLOT` 可用的分析类型
+const OUTPUT_SOURCES: [&str; 4] = ["AC", "DC", "NOISE", "TRAN"];
+
+/// 输出命令行上正在补全的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputField {
+    /// `.PRINT ` / `.PLOT ` 之后的分析类型
+    Source,
+    /// 分析类型（`.PROBE` 则为命令）之后的输出变量
+    Variable,
+}
+
+/// 识别 `.PRINT`/`.PLOT <analysis type> ` 与 `.PROBE ` 前缀，返回待补全字段与正在输入的部分
+///
+/// 命令可带 `/DGTLCHG`、`/CSDF` 等后缀。
+fn output_completions(prefix: &str) -> Option<(OutputField, &str)> {
+    let mut tokens: Vec<&str> = prefix.split_whitespace().collect();
+    let typed = if prefix.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        tokens.pop().unwrap_or("")
+    };
+    let command = tokens.first()?.split('/').next()?.to_uppercase();
+
+    match command.as_str() {
+        ".PRINT" | ".PLOT" if tokens.len() == 1 => Some((OutputField::Source, typed)),
+        ".PRINT" | ".PLOT" | ".PROBE" => Some((OutputField::Variable, typed)),
+        _ => None,
+    }
+}
+
+/// 分析类型候选：`AC`、`DC`、`NOISE`、`TRAN`
+fn output_source_items() -> Vec<CompletionItem> {
+    OUTPUT_SOURCES
+        .iter()
+        .map(|source| CompletionItem {
+            label: source.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("SPICE Analysis Type".to_string()),
+            insert_text: Some(format!("{} ", source)),
+            insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+            filter_text: Some(source.to_string()),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// 输出变量候选：`V(`/`I(` 模板（支持片段时带上电路中的节点/元件选项），
+/// 以及每个节点的 `V(node)` 和每个元件的 `I(device)`
+fn output_variable_items(
+    symbols: Option<&SymbolTable>,
+    line: usize,
+    snippet_support: bool,
+) -> Vec<CompletionItem> {
+    let nodes = symbols
+        .map(|s| s.node_names_in_scope(line))
+        .unwrap_or_default();
+    let mut devices: Vec<String> = symbols
+        .map(|s| {
+            s.table
+                .values()
+                .filter(|sym| matches!(sym.kind, SpiceSymbolKind::Component))
+                .map(|sym| sym.name.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    devices.sort();
+
+    let template = |function: &str, choices: &[String], detail: &str| {
+        let (insert_text, format) = if snippet_support && !choices.is_empty() {
+            (
+                format!("{}(${{1|{}|}})", function, choices.join(",")),
+                InsertTextFormat::SNIPPET,
+            )
+        } else {
+            (format!("{}(", function), InsertTextFormat::PLAIN_TEXT)
+        };
+        CompletionItem {
+            label: format!("{}(", function),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some(detail.to_string()),
+            insert_text: Some(insert_text),
+            insert_text_format: Some(format),
+            filter_text: Some(format!("{}(", function)),
+            ..Default::default()
+        }
+    };
+    let variable = |label: String, detail: &str| CompletionItem {
+        label: label.clone(),
+        kind: Some(CompletionItemKind::VARIABLE),
+        detail: Some(detail.to_string()),
+        insert_text: Some(label),
+        insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+        ..Default::default()
     };
 
-    // ${3|n1,n2,n3|}
-    let node_choices_3 = if names.is_empty() {
-        "${3|N1,N2,N3|}".to_string()
+    let mut items = vec![
+        template("V", &nodes, "Node voltage"),
+        template("I", &devices, "Device current"),
+    ];
+    items.extend(nodes.iter().map(|n| variable(format!("V({})", n), "Node voltage")));
+    items.extend(devices.iter().map(|d| variable(format!("I({})", d), "Device current")));
+    items
+}
+
+/// `.MODEL` 可用的模型类型及各类型常用参数
+const MODEL_TYPES: [(&str, &[&str]); 16] = [
+    ("RES", &["R", "TC1", "TC2", "TCE"]),
+    ("CAP", &["C", "VC1", "VC2", "TC1", "TC2"]),
+    ("IND", &["L", "IL1", "IL2", "TC1", "TC2"]),
+    ("D", &["IS", "N", "RS", "CJO", "VJ", "M", "TT", "BV", "IBV"]),
+    ("NPN", BJT_PARAMS),
+    ("PNP", BJT_PARAMS),
+    ("LPNP", BJT_PARAMS),
+    ("NJF", JFET_PARAMS),
+    ("PJF", JFET_PARAMS),
+    ("NMOS", MOS_PARAMS),
+    ("PMOS", MOS_PARAMS),
+    ("GASFET", &["LEVEL", "VTO", "ALPHA", "BETA", "LAMBDA", "B", "RD", "RS", "CGS", "CGD"]),
+    ("NIGBT", &["AGD", "AREA", "KP", "VT", "TAU", "WB", "MUN", "MUP"]),
+    ("CORE", &["LEVEL", "AREA", "PATH", "GAP", "MS", "A", "C", "K"]),
+    ("VSWITCH", &["RON", "ROFF", "VON", "VOFF"]),
+    ("ISWITCH", &["RON", "ROFF", "ION", "IOFF"]),
+];
+
+const BJT_PARAMS: &[&str] = &[
+    "IS", "BF", "NF", "VAF", "IKF", "ISE", "NE", "BR", "NR", "VAR", "IKR", "RB", "RE", "RC",
+    "CJE", "VJE", "MJE", "CJC", "VJC", "MJC", "TF", "TR",
+];
+const JFET_PARAMS: &[&str] = &["VTO", "BETA", "LAMBDA", "IS", "RD", "RS", "CGS", "CGD"];
+const MOS_PARAMS: &[&str] = &[
+    "LEVEL", "VTO", "KP", "GAMMA", "PHI", "LAMBDA", "RD", "RS", "CBD", "CBS", "IS", "TOX", "U0",
+];
+
+/// `.MODEL` 行上正在补全的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelField {
+    /// `.MODEL <name> ` 之后的模型类型
+    Type,
+    /// 给定类型之后的参数名
+    Param(&'static str),
+}
+
+/// `.MODEL <name> ` 之后补全模型类型，`.MODEL <name> <type> ` 之后补全该类型的参数名
+///
+/// 参数可写在括号内，已写过的参数不再提供；正在输入参数值（`IS=`）时不补全。
+fn model_completions(prefix: &str) -> Option<(ModelField, Vec<&'static str>, &str)> {
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, '(' | ')' | ',');
+    let mut tokens: Vec<&str> = prefix.split(is_separator).filter(|t| !t.is_empty()).collect();
+    let typed = if prefix.ends_with(is_separator) {
+        ""
     } else {
-        format!("${{3|{}|}}", names.join(","))
+        tokens.pop().unwrap_or("")
     };
+    if !tokens.first()?.eq_ignore_ascii_case(".MODEL") || typed.contains('=') {
+        return None;
+    }
+
+    match tokens.len() {
+        2 => Some((
+            ModelField::Type,
+            MODEL_TYPES.iter().map(|(name, _)| *name).collect(),
+            typed,
+        )),
+        n if n >= 3 => {
+            let (model_type, params) = MODEL_TYPES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(tokens[2]))?;
+            let written: Vec<String> = tokens[3..]
+                .iter()
+                .map(|t| t.split('=').next().unwrap_or_default().to_uppercase())
+                .collect();
+            let remaining = params
+                .iter()
+                .copied()
+                .filter(|p| !written.iter().any(|w| w == p))
+                .collect();
+            Some((ModelField::Param(model_type), remaining, typed))
+        }
+        _ => None,
+    }
+}
+
+/// 元件模板片段补全项；`data` 记下元件字母，resolve 时据此查文档
+fn snippet_item(line_text: &str, symbols: Option<&SymbolTable>, line: usize) -> Option<CompletionItem> {
+    let (label, snippet) = generate_snippet(line_text, symbols, line)?;
+    Some(CompletionItem {
+        label,
+        kind: Some(CompletionItemKind::SNIPPET),
+        insert_text: Some(snippet),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        data: line_text.get(..1).map(Into::into),
+        ..Default::default()
+    })
+}
+
+fn generate_snippet(str:&str,symbols: Option<&SymbolTable>, line: usize) -> Option<(String, String)> {
+
+    let names = node_candidates(symbols, line);
+    let choices = names.join(",");
+    // 第 i 个节点占位：${i|n1,n2,n3|}，每个节点使用独立的 tab stop
+    let node = |i: usize| format!("${{{}|{}|}}", i, choices);
 
-    //let unit_choices = "${5|Ω,kΩ,MΩ|}".to_string();
     match str.chars().next() {
-        Some('R') =>{ 
-            let snippet = node_choices_2.clone() + " "                          // (+) node
-                + &node_choices_3 + " "                                         // (-) node
-                + "${5:[model]} "                                               // [model name] 可跳过
-                + "${6:value} "                                                 // <value>
-                + "${7:[TC1]} "                                                 // [TC=<TC1>] 可跳过
-                + "${8:[TC2]}";        
+        Some('R') => {
+            let snippet = format!(
+                "{} {} ${{3:[model]}} ${{4:value}} ${{5:[TC1]}} ${{6:[TC2]}}",
+                node(1), // (+) node
+                node(2), // (-) node
+            );
             Some((
                 "R 模板:R<name> <(+) node> <(-) node> [model name] <value> [TC = <TC1> [,<TC2>]]".to_string(),
-                snippet,                                // [,<TC2>]   
+                snippet,
             ))
         }
         Some('C') => {
-            let snippet = node_choices_2.clone() + " "                          // (+) node
-                + &node_choices_3 + " "
-                + "${5:[model]} "                                               // [model name] 可跳过
-                + "${6:value} "
-                + "${7:[IC=<initial value>]}";
-
+            let snippet = format!(
+                "{} {} ${{3:[model]}} ${{4:value}} ${{5:[IC=<initial value>]}}",
+                node(1),
+                node(2),
+            );
             Some((
                 "C 模板:C<name> <(+) node> <(-) node> [model name] <value> [IC=<initial value>]".to_string(),
                 snippet,
             ))
         }
         Some('L') => {
-            let snippet = node_choices_2.clone() + " "                          // (+) node
-                + &node_choices_3 + " "
-                + "${5:[model]} "                                               // [model name] 可跳过
-                + "${6:value} "
-                + "${7:[IC=<initial value>]}";
+            let snippet = format!(
+                "{} {} ${{3:[model]}} ${{4:value}} ${{5:[IC=<initial value>]}}",
+                node(1),
+                node(2),
+            );
             Some((
                 "L 模板: L<name> <(+) node> <(-) node> [model name] <value> [IC=<initial value>]".to_string(),
                 snippet,
             ))
         }
         Some('B') => {
-            let snippet = node_choices_2.clone() + " "                          // (+) node
-                + &node_choices_3 + " "  
-                + &node_choices_3 + " "                        // <source node>
-                + "${6:model} "                                            // <model name>
-                + "[${7:}]";  
+            let snippet = format!(
+                "{} {} {} ${{4:model}} ${{5:[area value]}}",
+                node(1), // <drain node>
+                node(2), // <gate node>
+                node(3), // <source node>
+            );
             Some((
                 "砷化镓 MES 场效应晶体管: <name> <drain node> <gate node> <source node> <model name> [area value]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('D') => {
-            let snippet = node_choices_2.clone() + " "                          // (+) node
-                + &node_choices_3 + " "                          // <source node>
-                + "${6:model} "                                            // <model name>
-                + "[${7:area value}]";  
+            let snippet = format!("{} {} ${{3:model}} ${{4:[area value]}}", node(1), node(2));
             Some((
                 "二极管: D<name> <(+) node> <(-) node> <model name> [area value]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('E') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "                         
-                + "${4:gain value}"  ;  
+            let snippet = format!(
+                "{} {} {} {} ${{5:gain value}}",
+                node(1),
+                node(2),
+                node(3), // (+) controlling node
+                node(4), // (-) controlling node
+            );
             Some((
-                "电压源: E<name> <(+) node> <(-) node> <gain>".to_string(),
+                "电压控制电压源: E<name> <(+) node> <(-) node> <(+) controlling node> <(-) controlling node> <gain>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('F') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "                         
-                + "${4:gain value}"  ;  
+            let snippet = format!("{} {} ${{3:Vctrl}} ${{4:gain value}}", node(1), node(2));
             Some((
-                "电流控制电流源: F<name> <(+) node> <(-) node> <gain>".to_string(),
+                "电流控制电流源: F<name> <(+) node> <(-) node> <controlling V device name> <gain>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('G') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "                         
-                + "${4:gain value}"  ;  
+            let snippet = format!(
+                "{} {} {} {} ${{5:transconductance}}",
+                node(1),
+                node(2),
+                node(3), // (+) controlling node
+                node(4), // (-) controlling node
+            );
             Some((
-                "电流控制电流源: G<name> <(+) node> <(-) node> <gain>".to_string(),
+                "电压控制电流源: G<name> <(+) node> <(-) node> <(+) controlling node> <(-) controlling node> <transconductance>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('H') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "                         
-                + "${4:gain value}"  ;  
+            let snippet = format!("{} {} ${{3:Vctrl}} ${{4:transresistance}}", node(1), node(2));
             Some((
-                "电流控制电压源: H<name> <(+) node> <(-) node> <gain>".to_string(),
+                "电流控制电压源: H<name> <(+) node> <(-) node> <controlling V device name> <transresistance>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('I') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "                         
-                + "${3:[dc]} "
-                + "${4:[ac]} "
-                + "${5:[transient]}"  ;  
+            let snippet = format!(
+                "{} {} ${{3:[dc]}} ${{4:[ac]}} ${{5:[transient]}}",
+                node(1),
+                node(2),
+            );
             Some((
                 "独立电流源: I<name> <node1> <node2> [<dc>] [<ac>] [<transient>]".to_string(),
                 snippet,
-            ))    
+            ))
         }
- 
+
         Some('J') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + &node_choices_3 + " "                       
-                + "${3:model} "
-                + "${4:[area value]} ";  
+            let snippet = format!(
+                "{} {} {} ${{4:model}} ${{5:[area value]}}",
+                node(1),
+                node(2),
+                node(3),
+            );
             Some((
                 "结型场效应晶体管: J<name> <drain node> <gate node> <source node> <model name> [area value]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
-        //todo  L 的智能补全还未完成
         Some('K') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + &node_choices_3 + " "                       
-                + "${3:model} "
-                + "${4:[area value]} ";  
+            // 耦合的是电感元件而不是节点：候选取电路中已有的 L 元件
+            let mut inductors: Vec<String> = symbols
+                .map(|s| {
+                    s.table
+                        .values()
+                        .filter(|sym| sym.kind == SpiceSymbolKind::Component)
+                        .filter(|sym| sym.name.starts_with(['L', 'l']))
+                        .map(|sym| sym.name.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+            inductors.sort();
+            if inductors.is_empty() {
+                inductors = vec!["L1".to_string(), "L2".to_string()];
+            }
+            let inductor_choices = inductors.join(",");
+            let snippet = format!(
+                "${{1|{0}|}} ${{2|{0}|}} ${{3:coupling value}}",
+                inductor_choices
+            );
             Some((
-                "互感器(L 的智能补全还未完成): K<name> <induct1> <induct2> ... <k> [<model> [<size>]]".to_string(),
+                "互感器: K<name> <induct1> <induct2> ... <k> [<model> [<size>]]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('M') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + &node_choices_3 + " "
-                + &node_choices_3 + " "                       
-                + "${3:model} "
-                + " [L=${7}]"                     // optional L
-                + " [W=${8}]"                     // optional W
-                + " [AD=${9}] [AS=${10}]"         // optional AD/AS
-                + " [PD=${11}] [PS=${12}]"        // optional PD/PS
-                + " [NRD=${13}] [NRS=${14}]"      // optional NRD/NRS
-                + " [NRG=${15}] [NRB=${16}]"      // optional NRG/NRB
-                + " [M=${17}] [N=${18}]";  
-                      
+            let snippet = format!(
+                "{} {} {} {} ${{5:model}}",
+                node(1), // <drain node>
+                node(2), // <gate node>
+                node(3), // <source node>
+                node(4), // <bulk/substrate node>
+            ) + " [L=${6}]"                     // optional L
+                + " [W=${7}]"                     // optional W
+                + " [AD=${8}] [AS=${9}]"          // optional AD/AS
+                + " [PD=${10}] [PS=${11}]"        // optional PD/PS
+                + " [NRD=${12}] [NRS=${13}]"      // optional NRD/NRS
+                + " [NRG=${14}] [NRB=${15}]"      // optional NRG/NRB
+                + " [M=${16}] [N=${17}]";
+
             Some((
                 "MOS 场效应晶体管: M<name> <drain node> <gate node> <source node>`
                 + <bulk/substrate node> <model name>`
@@ -351,46 +820,52 @@ fn generate_snippet(str:&str,symbols: Option<&SymbolTable>,) -> Option<(String,
                 + [AD=<value>] [AS=<value>]`
                 + [PD=<value>] [PS=<value>]`
                 + [NRD=<value>] [NRS=<value>]`
-                + [NRG=<value>] [NRB=<value>]`Q
+                + [NRG=<value>] [NRB=<value>]`
                 + [M=<value>] [N=<value>]`".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('Q') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + &node_choices_3 + " ";  
+            let snippet = format!(
+                "{} {} {} ${{4:model}}",
+                node(1), // <collector node>
+                node(2), // <base node>
+                node(3), // <emitter node>
+            );
             Some((
-                "双极结型晶体管:Q<name> <collector node> <base node> <emitter node>".to_string(),
+                "双极结型晶体管:Q<name> <collector node> <base node> <emitter node> <model name>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('S') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "
-                + &node_choices_3 + " "
-                + &node_choices_3 + " "
-                + "${3:model} ";  
+            let snippet = format!(
+                "{} {} {} {} ${{5:model}}",
+                node(1),
+                node(2),
+                node(3),
+                node(4),
+            );
             Some((
                 "电压控制开关:S<name> <(+) switch node> <(-) switch node>`
                     <(+) controlling node> <(-) controlling node>`
                     <model name>`".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('T') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "
-                + &node_choices_3 + " "
-                + &node_choices_3 + " "
-                + "${3:model} "
-                + " Z0=${7:value}"                     // characteristic impedance
-                + " [TD=${8:value}]"                   // optional TD
-                + " [F=${9:value} [NL=${10:value}]]"   // optional F / NL
-                + " IC=${11:Vnear} ${12:Inear} ${13:Vfar} ${14:Ifar}"; // initial conditions;  
+            let snippet = format!(
+                "{} {} {} {} ${{5:[model]}}",
+                node(1),
+                node(2),
+                node(3),
+                node(4),
+            ) + " Z0=${6:value}"                     // characteristic impedance
+                + " [TD=${7:value}]"                   // optional TD
+                + " [F=${8:value} [NL=${9:value}]]"    // optional F / NL
+                + " IC=${10:Vnear} ${11:Inear} ${12:Vfar} ${13:Ifar}"; // initial conditions
             Some((
                 "输电线路:T<name> <A port (+) node> <A port (-) node>`
                      <B port (+) node> <B port (-) node>`
@@ -398,48 +873,48 @@ fn generate_snippet(str:&str,symbols: Option<&SymbolTable>,) -> Option<(String,
                      Z0=<value> [TD=<value>] [F=<value> [NL=<value>]]`
                      IC= <near voltage> <near current> <far voltage> <far current>`".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('V') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + "${3:[dc]} "
-                + "${4:[ac]} "
-                + "${5:[transient]}";  
+            let snippet = format!(
+                "{} {} ${{3:[dc]}} ${{4:[ac]}} ${{5:[transient]}}",
+                node(1),
+                node(2),
+            );
             Some((
                 "独立电压源:V<name> <node1> <node2> [<dc>] [<ac>] [<transient>]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         Some('W') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + " ${4:Vctrl}"              // controlling voltage device name
-                + " ${5:model}";             // model name;  
+            let snippet = format!(
+                "{} {} ${{3:Vctrl}} ${{4:model}}", // controlling voltage device name, model name
+                node(1),
+                node(2),
+            );
             Some((
                 "电流控制开关:W<name> <(+) switch node> <(-) switch node> <controlling V device name> <model name>".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
-        // todo 还没写 X
-         Some('X') => {
-            let snippet = node_choices_2.clone() + " "                         
-                + &node_choices_3 + " "  
-                + " ${4:Vctrl}"              // controlling voltage device name
-                + " ${5:model}";             // model name;  
+        Some('X') => {
+            // 节点个数由子电路定义决定，这里给出两个节点，多余的可删除
+            let snippet = format!(
+                "{} {} ${{3:subcircuit name}} ${{4:[PARAMS: <name>=<value>]}}",
+                node(1),
+                node(2),
+            );
             Some((
                 "调用子电路:X<name> [node]* <subcircuit name> [PARAM: <<name> = <value>>*]".to_string(),
                 snippet,
-            ))    
+            ))
         }
 
         _ => None,
     }
-    
-
 }
 
 fn generate_component_completions(str:&str) -> Vec<String>{
@@ -476,6 +951,71 @@ fn generate_component_completions(str:&str) -> Vec<String>{
 
 
 
+/// 对光标前的文本做部分解析；光标紧贴未写完的单词时不处理
+fn partial_parse_line(prefix: &str) -> Option<ComponentPartial> {
+    if !prefix.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let lines = SpiceLexer::tokenize(prefix);
+    let tokens = lines.first()?;
+    let mut parser = SpiceLineParser::new(tokens);
+    PartialParse::<ComponentPartial>::info(&mut parser)
+        .ok()
+        .map(|(partial, _)| partial)
+}
+
+/// 根据部分解析结果给出下一个待填字段的候选，以及描述当前行解释的 detail，
+/// 例如 `R1 1 2 ` → `R1: N1=1 N2=2, completing <value>`
+fn next_field_completions(
+    partial: &ComponentPartial,
+    symbols: Option<&SymbolTable>,
+    line: usize,
+) -> Option<(String, Vec<(String, CompletionRank)>)> {
+    let (name, node1, node2, value_missing, values): (_, _, _, _, &[&str]) = match partial {
+        ComponentPartial::R(p) => (&p.name, &p.node1, &p.node2, p.value.is_none(), &["1k", "10k", "100"]),
+        ComponentPartial::C(p) => (&p.name, &p.node1, &p.node2, p.value.is_none(), &["1uF", "10uF"]),
+        ComponentPartial::L(p) => (&p.name, &p.node1, &p.node2, p.value.is_none(), &["1uH", "10uH", "100nH", "1mH"]),
+        _ => return None,
+    };
+    let name = name.as_ref()?;
+
+    let (field, candidates) = if node1.is_none() || node2.is_none() {
+        let field = if node1.is_none() { "<node1>" } else { "<node2>" };
+        let circuit = symbols.map(|s| s.get_node_names()).unwrap_or_default();
+        let candidates = node_candidates(symbols, line)
+            .into_iter()
+            .map(|n| {
+                let rank = if circuit.contains(&n) {
+                    CompletionRank::Circuit
+                } else {
+                    CompletionRank::Fallback
+                };
+                (n, rank)
+            })
+            .collect();
+        (field, candidates)
+    } else if value_missing {
+        let candidates = values
+            .iter()
+            .map(|v| (v.to_string(), CompletionRank::Fallback))
+            .collect();
+        ("<value>", candidates)
+    } else {
+        return None;
+    };
+
+    let parsed: Vec<String> = [("N1", node1), ("N2", node2)]
+        .into_iter()
+        .filter_map(|(label, node)| node.as_ref().map(|n| format!("{}={}", label, n.0)))
+        .collect();
+    let detail = if parsed.is_empty() {
+        format!("{}: completing {}", name.0, field)
+    } else {
+        format!("{}: {}, completing {}", name.0, parsed.join(" "), field)
+    };
+    Some((detail, candidates))
+}
+
 fn generate_completions_from_partial(
     partial: &ComponentPartial,
     cursor_pos: usize,
@@ -661,6 +1201,304 @@ fn generate_completions_from_partial(
     completions
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_info::symbol::Symbol;
+
+    fn table_with_nodes(nodes: &[&str]) -> SymbolTable {
+        let mut table = SymbolTable::new(Url::parse("file:///test.cir").unwrap());
+        for (i, name) in nodes.iter().enumerate() {
+            table.table.insert(
+                name.to_string(),
+                Symbol {
+                    name: name.to_string(),
+                    range: Range {
+                        start: Position::new(i as u32, 3),
+                        end: Position::new(i as u32, 3 + name.len() as u32),
+                    },
+                    kind: SpiceSymbolKind::Node,
+                    container: None,
+                    refcnt: 0,
+                },
+            );
+        }
+        table
+    }
+
+    #[test]
+    fn test_circuit_nodes_before_fallback() {
+        let table = table_with_nodes(&["OUT", "IN"]);
+        let names = node_candidates(Some(&table), 0);
+        assert_eq!(names, vec!["IN", "OUT", "N1", "N2", "N3"]);
+
+        let (_, snippet) = generate_snippet("R1 ", Some(&table), 0).unwrap();
+        assert!(snippet.starts_with("${1|IN,OUT,N1,N2,N3|} ${2|IN,OUT,N1,N2,N3|}"));
+    }
+
+    #[test]
+    fn test_resistor_snippet() {
+        let (label, snippet) = generate_snippet("R1 ", None, 0).unwrap();
+        assert!(label.starts_with("R 模板"));
+        assert_eq!(
+            snippet,
+            "${1|N1,N2,N3|} ${2|N1,N2,N3|} ${3:[model]} ${4:value} ${5:[TC1]} ${6:[TC2]}"
+        );
+    }
+
+    #[test]
+    fn test_vccs_snippet() {
+        let table = table_with_nodes(&["IN", "OUT"]);
+        let (label, snippet) = generate_snippet("G1 ", Some(&table), 0).unwrap();
+        assert!(label.starts_with("电压控制电流源"));
+        // 四个节点各自独立，不会互相镜像
+        for i in 1..=4 {
+            assert!(snippet.contains(&format!("${{{}|IN,OUT,N1,N2,N3|}}", i)));
+        }
+        assert!(snippet.ends_with("${5:transconductance}"));
+    }
+
+    #[test]
+    fn test_subckt_call_snippet() {
+        let (label, snippet) = generate_snippet("X1 ", None, 0).unwrap();
+        assert!(label.starts_with("调用子电路"));
+        assert_eq!(
+            snippet,
+            "${1|N1,N2,N3|} ${2|N1,N2,N3|} ${3:subcircuit name} ${4:[PARAMS: <name>=<value>]}"
+        );
+    }
+
+    #[test]
+    fn test_partial_line_detail() {
+        let partial = partial_parse_line("R1 1 2 ").unwrap();
+        let (detail, candidates) = next_field_completions(&partial, None, 0).unwrap();
+        assert_eq!(detail, "R1: N1=1 N2=2, completing <value>");
+        assert_eq!(candidates[0].0, "1k");
+
+        let table = table_with_nodes(&["IN", "OUT"]);
+        let partial = partial_parse_line("C1 IN ").unwrap();
+        let (detail, candidates) = next_field_completions(&partial, Some(&table), 0).unwrap();
+        assert_eq!(detail, "C1: N1=IN, completing <node2>");
+        assert_eq!(candidates[0], ("IN".to_string(), CompletionRank::Circuit));
+        assert_eq!(candidates[2], ("N1".to_string(), CompletionRank::Fallback));
+
+        // 光标紧贴单词时不做部分解析
+        assert!(partial_parse_line("R1 1 2").is_none());
+    }
+
+    #[test]
+    fn test_print_offers_node_voltages() {
+        assert_eq!(output_completions(".PRINT "), Some((OutputField::Source, "")));
+        assert_eq!(output_completions(".plot TR"), Some((OutputField::Source, "TR")));
+        assert_eq!(output_completions(".PRINT TRAN "), Some((OutputField::Variable, "")));
+        assert_eq!(output_completions(".PROBE/CSDF V"), Some((OutputField::Variable, "V")));
+        assert!(output_completions(".TRAN 1n ").is_none());
+
+        let table = table_with_nodes(&["IN", "OUT"]);
+        let items = output_variable_items(Some(&table), 0, true);
+        assert_eq!(items[0].label, "V(");
+        assert_eq!(items[0].insert_text.as_deref(), Some("V(${1|IN,OUT|})"));
+        assert_eq!(items[0].insert_text_format, Some(InsertTextFormat::SNIPPET));
+        assert!(items.iter().any(|i| i.label == "V(OUT)"));
+
+        // 分析类型与输出变量不会按首字母（`DC` → 二极管、`V(` → 电压源）套用元件文档
+        for item in output_source_items().into_iter().chain(items) {
+            assert!(resolve_item(item, true).documentation.is_none());
+        }
+
+        // 无片段支持或电路中没有元件时退回纯文本模板
+        let items = output_variable_items(Some(&table), 0, false);
+        assert_eq!(items[0].insert_text.as_deref(), Some("V("));
+        assert_eq!(items[1].insert_text.as_deref(), Some("I("));
+    }
+
+    #[test]
+    fn test_model_type_and_param_completions() {
+        let (field, candidates, typed) = model_completions(".MODEL Q1 N").unwrap();
+        assert_eq!(field, ModelField::Type);
+        assert_eq!(typed, "N");
+        assert!(candidates.contains(&"NPN") && candidates.contains(&"RES"));
+
+        let (field, candidates, _) = model_completions(".MODEL Q1 NPN ").unwrap();
+        assert_eq!(field, ModelField::Param("NPN"));
+        assert!(candidates.contains(&"IS") && candidates.contains(&"BF"));
+
+        // 括号内已写过的参数不再提供，正在填值时不补全
+        let (_, candidates, _) = model_completions(".model q1 npn(IS=1e-16, ").unwrap();
+        assert!(!candidates.contains(&"IS") && candidates.contains(&"BF"));
+        assert!(model_completions(".MODEL Q1 NPN (IS=").is_none());
+        assert!(model_completions(".MODEL Q1 FOO ").is_none());
+        assert!(model_completions(".TRAN 1n ").is_none());
+    }
+
+    #[test]
+    fn test_model_items_keep_own_documentation() {
+        let item = CompletionItem {
+            label: "IS".to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            ..Default::default()
+        };
+        assert!(resolve_item(item, true).documentation.is_none());
+    }
+
+    #[test]
+    fn test_fallback_nodes_not_duplicated() {
+        let table = table_with_nodes(&["n1", "VCC"]);
+        assert_eq!(node_candidates(Some(&table), 0), vec!["VCC", "n1", "N2", "N3"]);
+        assert_eq!(node_candidates(None, 0), vec!["N1", "N2", "N3"]);
+    }
+
+    #[test]
+    fn test_exact_prefix_sorts_first() {
+        let mut items: Vec<CompletionItem> = generate_component_completions("V")
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let rank = rank_for(&name, "V", CompletionRank::Fallback);
+                ranked_item(
+                    CompletionItem {
+                        label: name,
+                        ..Default::default()
+                    },
+                    rank,
+                    i,
+                )
+            })
+            .collect();
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, vec!["V", "R", "C", "L", "I"]);
+        assert_eq!(items[0].filter_text.as_deref(), Some("V"));
+    }
+
+    #[test]
+    fn test_snippet_filters_on_label() {
+        let item = snippet_item("R1 ", None, 0).unwrap();
+        let label = item.label.clone();
+        let item = ranked_item(item, CompletionRank::Circuit, 0);
+        assert_eq!(item.filter_text, Some(label));
+    }
+
+    #[test]
+    fn test_resolve_snippet_by_component_letter() {
+        // 片段的 label 以中文说明开头，文档按 `data` 中的元件字母查
+        for (line_text, summary, syntax) in [
+            ("Q1 ", "双极结型晶体管", "Q<name> <collector node>"),
+            ("X1 ", "子电路调用", "X<name> [node]* <subcircuit name>"),
+        ] {
+            let item = snippet_item(line_text, None, 0).unwrap();
+            assert!(!item.label.starts_with(&line_text[..1]));
+            let resolved = resolve_item(item, true);
+            assert_eq!(resolved.detail.as_deref(), Some(summary));
+            match resolved.documentation {
+                Some(Documentation::MarkupContent(content)) => assert!(content.value.contains(syntax)),
+                other => panic!("expected markdown documentation, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve_tran_documentation() {
+        let item = CompletionItem {
+            label: ".TRAN".to_string(),
+            ..Default::default()
+        };
+        let resolved = resolve_item(item, true);
+        assert_eq!(resolved.detail.as_deref(), Some("瞬态分析"));
+        match resolved.documentation {
+            Some(Documentation::MarkupContent(content)) => {
+                assert_eq!(content.kind, MarkupKind::Markdown);
+                assert!(content.value.contains(".TRAN[/OP] <print step value> <final time value>"));
+            }
+            other => panic!("expected markdown documentation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_component_by_letter() {
+        let item = CompletionItem {
+            label: "R1".to_string(),
+            kind: Some(CompletionItemKind::TEXT),
+            detail: Some("SPICE Component".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolve_item(item, false);
+        // 已有 detail 不被覆盖；不支持 Markdown 的客户端收到纯文本
+        assert_eq!(resolved.detail.as_deref(), Some("SPICE Component"));
+        assert!(matches!(resolved.documentation, Some(Documentation::String(ref s)) if s.contains("R<name>")));
+
+        let unknown = resolve_item(
+            CompletionItem {
+                label: "PARAMS:".to_string(),
+                kind: Some(CompletionItemKind::TEXT),
+                ..Default::default()
+            },
+            true,
+        );
+        assert!(unknown.documentation.is_none());
+    }
+
+    #[test]
+    fn test_non_component_items_have_no_documentation() {
+        // 首字母恰好是元件字母的关键字与节点名不能套用元件文档
+        for (label, kind) in [
+            ("TEXT:", CompletionItemKind::KEYWORD),
+            ("DC", CompletionItemKind::KEYWORD),
+            ("IN", CompletionItemKind::VALUE),
+            ("MID", CompletionItemKind::VALUE),
+        ] {
+            let item = CompletionItem {
+                label: label.to_string(),
+                kind: Some(kind),
+                ..Default::default()
+            };
+            let resolved = resolve_item(item, true);
+            assert!(resolved.documentation.is_none(), "{} got documentation", label);
+            assert!(resolved.detail.is_none());
+        }
+    }
+
+    #[test]
+    fn test_cursor_prefix_after_astral_chars() {
+        // 😀 占两个 UTF-16 码元，按字符数截取会把光标后的 `P` 也带进来
+        let line = "X😀 IN OUT FILTER P";
+        assert_eq!(cursor_prefix(line, 18), "X😀 IN OUT FILTER ");
+        let (sections, typed) = section_keyword_completions(cursor_prefix(line, 18)).unwrap();
+        assert_eq!((sections[0], typed), ("PARAMS:", ""));
+        assert_eq!(cursor_prefix(line, 100), line);
+    }
+
+    #[test]
+    fn test_subckt_sections_after_pins() {
+        let (sections, typed) = section_keyword_completions(".SUBCKT FILTER IN OUT ").unwrap();
+        assert_eq!(sections, vec!["OPTIONAL:", "PARAMS:", "TEXT:"]);
+        assert_eq!(typed, "");
+
+        let (sections, typed) = section_keyword_completions(".subckt F 1 2 PA").unwrap();
+        assert!(sections.contains(&"PARAMS:"));
+        assert_eq!(typed, "PA");
+
+        // 缺少引脚时不提供
+        assert!(section_keyword_completions(".SUBCKT FILTER ").is_none());
+    }
+
+    #[test]
+    fn test_sections_after_written_section() {
+        let (sections, _) =
+            section_keyword_completions(".SUBCKT F 1 2 PARAMS: CENTER=100kHz ").unwrap();
+        assert_eq!(sections, vec!["TEXT:"]);
+        assert!(section_keyword_completions(".SUBCKT F 1 2 TEXT: A=\"x\" ").is_none());
+    }
+
+    #[test]
+    fn test_x_call_sections() {
+        let (sections, _) = section_keyword_completions("X1 IN OUT FILTER ").unwrap();
+        assert_eq!(sections, vec!["PARAMS:", "TEXT:"]);
+        assert!(section_keyword_completions("X1 IN ").is_none());
+        assert!(section_keyword_completions("R1 1 2 1k ").is_none());
+    }
+}
+
 #[test]
 fn test_partial_parse_resistor() {
     // 测试部分解析一个不完整的电阻组件定义
@@ -0,0 +1,145 @@
+use crate::netlist::statements;
+use crate::position::byte_offset_to_lsp_position;
+use crate::state::SharedServerState;
+use tower_lsp::lsp_types::*;
+
+/// 文档中的一条 `.MODEL` 定义
+pub(crate) struct ModelDefinition {
+    /// 模型名（大写，SPICE 不区分大小写）
+    name: String,
+    /// 所在子电路的嵌套路径，顶层为空
+    scope: Vec<String>,
+    /// 模型类型（大写），如 `NPN`、`D`
+    pub(crate) kind: String,
+    /// 模型名在全文中的字节范围
+    start: usize,
+    end: usize,
+}
+
+pub async fn on_goto_definition(
+    state: SharedServerState,
+    params: GotoDefinitionParams,
+) -> Result<Option<GotoDefinitionResponse>, tower_lsp::jsonrpc::Error> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let text = {
+        let s = state.lock().await;
+        match s.documents.get(&uri) {
+            Some(doc) => doc.text.clone(),
+            None => return Ok(None),
+        }
+    };
+
+    Ok(find_model_definition(&text, position)
+        .map(|range| GotoDefinitionResponse::Scalar(Location { uri, range })))
+}
+
+/// 光标位于元件语句中的模型名（如 `QAP 4 3 19 QPMOD` 的 `QPMOD`）时，返回其 `.MODEL` 定义的范围
+///
+/// 先在光标所在子电路中查找，再逐层向外；未定义（内置）模型返回 `None`。
+fn find_model_definition(text: &str, position: Position) -> Option<Range> {
+    let definitions = collect_models(text);
+
+    let (statement, index) = statements(text).into_iter().find_map(|statement| {
+        let index = statement.words.iter().position(|w| {
+            w.range.start.line == position.line
+                && w.range.start.character <= position.character
+                && position.character <= w.range.end.character
+        })?;
+        Some((statement, index))
+    })?;
+    // 语句首词是元件名或命令关键字，不是模型引用
+    if index == 0 {
+        return None;
+    }
+    let name = statement.words[index].text.to_uppercase();
+
+    resolve_model(&definitions, &statement.scope, &name).map(|def| Range {
+        start: byte_offset_to_lsp_position(text, def.start),
+        end: byte_offset_to_lsp_position(text, def.end),
+    })
+}
+
+/// 按作用域查找模型（`name` 为大写）：先在 `scope` 所指的子电路中找，再逐层向外
+pub(crate) fn resolve_model<'a>(
+    definitions: &'a [ModelDefinition],
+    scope: &[String],
+    name: &str,
+) -> Option<&'a ModelDefinition> {
+    (0..=scope.len()).rev().find_map(|depth| {
+        definitions
+            .iter()
+            .find(|def| def.name == name && def.scope == scope[..depth])
+    })
+}
+
+/// 收集所有 `.MODEL` 定义
+pub(crate) fn collect_models(text: &str) -> Vec<ModelDefinition> {
+    statements(text)
+        .into_iter()
+        .filter(|statement| statement.keyword() == ".MODEL")
+        .filter_map(|statement| {
+            let name = statement.words.get(1)?;
+            Some(ModelDefinition {
+                name: name.text.to_uppercase(),
+                scope: statement.scope.clone(),
+                kind: statement
+                    .words
+                    .get(2)
+                    .map(|w| w.text.to_uppercase())
+                    .unwrap_or_default(),
+                start: name.offset,
+                end: name.offset + name.text.len(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETLIST: &str = "\
+* regulator
+.MODEL QPMOD PNP (BF=50)
+.SUBCKT REG 1 2 3
+QAP 4 3 19 QPMOD
+.MODEL qpmod PNP (BF=80)
+.ENDS
+QX 1 2 3 QPMOD ; top level
+DX 1 2 DMISSING
+";
+
+    #[test]
+    fn test_model_in_subckt_resolves_locally() {
+        // 光标在 `QAP 4 3 19 QPMOD` 的 QPMOD 上
+        let range = find_model_definition(NETLIST, Position::new(3, 12)).unwrap();
+        assert_eq!(range.start, Position::new(4, 7));
+        assert_eq!(range.end, Position::new(4, 12));
+    }
+
+    #[test]
+    fn test_model_at_top_level_resolves_globally() {
+        let range = find_model_definition(NETLIST, Position::new(6, 9)).unwrap();
+        assert_eq!(range.start, Position::new(1, 7));
+        assert_eq!(range.end, Position::new(1, 12));
+    }
+
+    #[test]
+    fn test_undefined_model_and_non_model_words() {
+        assert!(find_model_definition(NETLIST, Position::new(7, 10)).is_none());
+        // 元件名本身、注释不算模型引用
+        assert!(find_model_definition(NETLIST, Position::new(6, 0)).is_none());
+        assert!(find_model_definition(NETLIST, Position::new(6, 20)).is_none());
+    }
+
+    #[test]
+    fn test_model_on_continuation_line() {
+        let source = "* amp\n.MODEL QN NPN\nQ1 C B\n+ E QN\n";
+        let range = find_model_definition(source, Position::new(3, 5)).unwrap();
+        assert_eq!(range.start, Position::new(1, 7));
+        // 续行上的节点不是模型引用
+        assert!(find_model_definition(source, Position::new(3, 2)).is_none());
+    }
+}
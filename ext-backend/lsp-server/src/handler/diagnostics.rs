@@ -1,5 +1,10 @@
+use super::definition::{collect_models, resolve_model};
+use crate::netlist::{Statement, is_title_line, statements};
+use crate::position::{byte_offset_to_lsp_position, utf16_column};
 use crate::state::{DocumentState, SharedServerState};
 use crate::symbol_info::table::SymbolTable;
+use crate::symbol_info::terminal::node_token_range;
+use std::collections::{HashMap, HashSet};
 use spice_parser_core::try_parse_program;
 use tower_lsp::Client;
 use tower_lsp::lsp_types::*;
@@ -17,8 +22,8 @@ pub async fn on_did_open(
         //s.documents.insert(uri.clone(), text.clone());
         let doc_state = DocumentState {
             text,
-            ast: None,
-            symbols: None,
+            version: params.text_document.version,
+            ..Default::default()
         };
         s.documents.insert(uri.clone(), doc_state);
     }
@@ -64,6 +69,7 @@ pub async fn on_did_change(
     // 使用增量同步，处理多个变更
     let mut s = state.lock().await;
     if let Some(doc) = s.documents.get_mut(&uri) {
+        doc.version = params.text_document.version;
         if let Some(first) = params.content_changes.first() {
             match &first.range {
                 None => {
@@ -105,12 +111,40 @@ pub async fn on_did_change(
     reparse_and_publish(client, state, uri).await;
 }
 
+/// 关闭文档：移出缓存并清空该文档的诊断
+pub async fn on_did_close(
+    client: &Client,
+    state: SharedServerState,
+    params: DidCloseTextDocumentParams,
+) {
+    let uri = params.text_document.uri;
+    state.lock().await.documents.remove(&uri);
+    client.publish_diagnostics(uri, vec![], None).await;
+}
+
+/// 保存：客户端带回全文时以其为准，然后重新解析并发布诊断
+pub async fn on_did_save(
+    client: &Client,
+    state: SharedServerState,
+    params: DidSaveTextDocumentParams,
+) {
+    let uri = params.text_document.uri;
+    if let Some(text) = params.text {
+        let mut s = state.lock().await;
+        if let Some(doc) = s.documents.get_mut(&uri) {
+            doc.text = text;
+        }
+    }
+
+    reparse_and_publish(client, state, uri).await;
+}
+
 async fn reparse_and_publish(client: &Client, state: SharedServerState, uri: Url) {
-    let source = {
+    let (source, version) = {
         let s = state.lock().await;
         s.documents
             .get(&uri)
-            .map(|doc| doc.text.clone())
+            .map(|doc| (doc.text.clone(), Some(doc.version)))
             .unwrap_or_default()
     };
 
@@ -118,7 +152,8 @@ async fn reparse_and_publish(client: &Client, state: SharedServerState, uri: Url
         Ok(program) => {
             // 预先构建符号表与统计信息（锁外执行，避免锁内重活）
             let instr_count = program.instructions.len();
-            let symbols_built = SymbolTable::build_from_ast(uri.clone(), program.clone());
+            let mut symbols_built = SymbolTable::build_from_ast(uri.clone(), program.clone());
+            symbols_built.collect_subckts(&source);
             let symbol_count = symbols_built.table.len();
 
             // 缓存 AST & 符号表（仅内存写入在锁内）
@@ -130,8 +165,10 @@ async fn reparse_and_publish(client: &Client, state: SharedServerState, uri: Url
                 }
             }
 
-            // 清空诊断（或基于 AST 生成真正的诊断）
-            //client.publish_diagnostics(uri, vec![], None).await;
+            // 解析成功：以语义检查结果替换上一次留下的解析错误
+            client
+                .publish_diagnostics(uri.clone(), analysis_diagnostics(&source), version)
+                .await;
 
             // 成功日志（锁外）：确认 AST 与符号表已构建
             client
@@ -145,22 +182,9 @@ async fn reparse_and_publish(client: &Client, state: SharedServerState, uri: Url
                 .await;
         }
         Err(err) => {
-            let (line, col) = err.position.unwrap_or((0, 0));
-
-            let line_text = source.lines().nth(line).unwrap_or("");
-            let word_len = extract_word(line_text, col).unwrap_or(1); // 默认长度 1
-
-            let diag = Diagnostic {
-                range: Range {
-                    start: Position::new(line as u32, col as u32),
-                    end: Position::new(line as u32, col as u32 + word_len as u32),
-                },
-                severity: Some(DiagnosticSeverity::ERROR),
-                message: err.reason,
-                ..Default::default()
-            };
+            let diag = parse_error_diagnostic(&source, err.position, err.reason);
             client
-                .publish_diagnostics(uri.clone(), vec![diag], None)
+                .publish_diagnostics(uri.clone(), vec![diag], version)
                 .await;
 
             client.log_message(MessageType::ERROR, "Parse failed").await;
@@ -168,32 +192,332 @@ async fn reparse_and_publish(client: &Client, state: SharedServerState, uri: Url
     }
 }
 
-fn extract_word(line_text: &str, location: usize) -> Option<usize> {
-    let chars: Vec<char> = line_text.chars().collect();
+/// 解析通过后的语义检查：引用了未定义模型或类型不符的模型的元件、
+/// 同一作用域内重名的元件、只连到一个端子的悬空节点、重复或接地的子电路引脚、
+/// 缺失、重复或不在末尾的 `.END`
+///
+/// 作用域按 `.SUBCKT`/`.ENDS` 划分；模型先在所在子电路中查找，再逐层向外。
+/// 文件用 `.LIB`/`.INC` 引入外部文件时不检查模型，模型可能定义在那里。
+/// 第一行是标题，不参与检查；子电路端口和地节点 `0` 不算悬空。
+/// 接到 `X` 调用引脚的节点经子电路端口计算连接数，子电路不在本文件中时视为已连接。
+fn analysis_diagnostics(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut components: HashSet<(Vec<String>, String)> = HashSet::new();
+    let mut ports: HashSet<(Vec<String>, String)> = HashSet::new();
+    // 子电路名 → (内部作用域, 端口)
+    let mut subckts: HashMap<String, (Vec<String>, Vec<String>)> = HashMap::new();
+    // 节点 → 各次出现，Vec 保持首次出现的顺序
+    let mut node_uses: HashMap<(Vec<String>, String), Vec<NodeUse>> = HashMap::new();
+    let mut node_order: Vec<((Vec<String>, String), String)> = Vec::new();
+    let models = collect_models(source);
+    let mut undefined_models = Vec::new();
+    let mut includes_files = false;
+
+    let statements = statements(source);
+    for statement in &statements {
+        let texts = statement.texts();
+        let scope = &statement.scope;
+        let first = texts[0];
+
+        match statement.keyword().as_str() {
+            ".SUBCKT" => {
+                let mut inner = scope.clone();
+                inner.push(texts.get(1).unwrap_or(&"").to_uppercase());
+                diagnostics.extend(pin_diagnostics(statement, texts.get(1).unwrap_or(&"")));
+                let pins: Vec<String> = statement
+                    .subckt_pins()
+                    .iter()
+                    .map(|pin| pin.text.to_uppercase())
+                    .collect();
+                for pin in &pins {
+                    ports.insert((inner.clone(), pin.clone()));
+                }
+                subckts.insert(inner.last().cloned().unwrap_or_default(), (inner, pins));
+            }
+            ".LIB" | ".INC" | ".INCLUDE" => includes_files = true,
+            _ if first.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                let candidates = model_token_candidates(&texts);
+                // 有衬底节点时模型在后，优先取靠后的候选
+                let model = candidates.iter().rev().find_map(|&idx| {
+                    resolve_model(&models, scope, &texts[idx].to_uppercase()).map(|def| (idx, def))
+                });
+                match model {
+                    Some((idx, def)) => {
+                        if let Some(expected) = expected_model_types(first)
+                            && !def.kind.starts_with("AKO")
+                            && !expected.contains(&def.kind.as_str())
+                        {
+                            diagnostics.push(Diagnostic {
+                                range: statement.words[idx].range,
+                                severity: Some(DiagnosticSeverity::WARNING),
+                                source: Some("spice".to_string()),
+                                message: format!(
+                                    "`{}` expects a {} model, but `{}` is a {} model",
+                                    first,
+                                    expected.join("/"),
+                                    texts[idx],
+                                    def.kind
+                                ),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    None => {
+                        if let Some(&report) = candidates.last() {
+                            undefined_models.push(Diagnostic {
+                                range: statement.words[report].range,
+                                severity: Some(DiagnosticSeverity::ERROR),
+                                source: Some("spice".to_string()),
+                                message: format!("Undefined model `{}`", texts[report]),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
 
-    // 检查location是否在有效范围内
-    if location >= chars.len() {
-        return Some(1); // 默认长度1
+                let name = (scope.clone(), first.to_uppercase());
+                if !components.insert(name) {
+                    diagnostics.push(Diagnostic {
+                        range: statement.words[0].range,
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        source: Some("spice".to_string()),
+                        message: format!("Duplicate component name `{}`", first),
+                        ..Default::default()
+                    });
+                }
+
+                let nodes = node_token_range(&texts);
+                let instance_of = first
+                    .starts_with(['X', 'x'])
+                    .then(|| texts.get(nodes.end).map(|t| t.to_uppercase()))
+                    .flatten();
+                for idx in nodes {
+                    let key = (scope.clone(), texts[idx].to_uppercase());
+                    let uses = node_uses.entry(key.clone()).or_default();
+                    if uses.is_empty() {
+                        node_order.push((key, texts[idx].to_string()));
+                    }
+                    uses.push(NodeUse {
+                        range: statement.words[idx].range,
+                        pin_of: instance_of.clone().map(|subckt| (subckt, idx - 1)),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !includes_files {
+        diagnostics.extend(undefined_models);
     }
+    diagnostics.extend(end_diagnostics(source, &statements));
 
-    if chars[location] != ' ' {
-        let mut start = location;
-        while start > 0 && chars[start - 1] != ' ' {
-            start -= 1;
+    for (key, node) in node_order {
+        if key.1 == "0" || ports.contains(&key) {
+            continue;
         }
+        let uses = &node_uses[&key];
+        let connections: usize = uses
+            .iter()
+            .map(|u| match &u.pin_of {
+                None => 1,
+                // 经子电路端口连到的内部端子数；找不到子电路时按已连接处理
+                Some((subckt, pin)) => subckts
+                    .get(subckt)
+                    .and_then(|(inner, pins)| pins.get(*pin).map(|p| (inner.clone(), p.clone())))
+                    .map_or(2, |inner_key| {
+                        node_uses.get(&inner_key).map_or(0, Vec::len)
+                    }),
+            })
+            .sum();
+        if connections <= 1 {
+            diagnostics.push(Diagnostic {
+                range: uses[0].range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some("spice".to_string()),
+                message: format!("Node `{}` is connected to only one terminal", node),
+                ..Default::default()
+            });
+        }
+    }
+    diagnostics
+}
 
-        let mut end = location;
-        while end < chars.len() && chars[end] != ' ' {
-            end += 1;
+/// `.END` 检查：缺少 `.END` 时在文末警告；`.END` 重复，或之后还有语句（会被忽略）时报错
+///
+/// 没有标题的文件是被 `.INC`/`.LIB` 引入的库文件，本就没有 `.END`。
+fn end_diagnostics(source: &str, statements: &[Statement]) -> Vec<Diagnostic> {
+    let ends: Vec<usize> = statements
+        .iter()
+        .enumerate()
+        .filter(|(_, statement)| statement.keyword() == ".END")
+        .map(|(i, _)| i)
+        .collect();
+    let Some(&first) = ends.first() else {
+        let is_library = !source.lines().next().is_some_and(|line| is_title_line(0, line));
+        if is_library {
+            return vec![];
         }
+        let end = byte_offset_to_lsp_position(source, source.len());
+        return vec![Diagnostic {
+            range: Range::new(end, end),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("spice".to_string()),
+            message: "Missing `.END`".to_string(),
+            ..Default::default()
+        }];
+    };
 
-        //左闭右开
-        Some(end - start)
-    } else {
-        None
+    let mut diagnostics = Vec::new();
+    if statements[first + 1..]
+        .iter()
+        .any(|statement| statement.keyword() != ".END")
+    {
+        diagnostics.push(Diagnostic {
+            range: statements[first].words[0].range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("spice".to_string()),
+            message: "Statements after `.END` are ignored".to_string(),
+            ..Default::default()
+        });
+    }
+    for &i in &ends[1..] {
+        diagnostics.push(Diagnostic {
+            range: statements[i].words[0].range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("spice".to_string()),
+            message: "Duplicate `.END`".to_string(),
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+/// `.SUBCKT` 引脚检查：重复的引脚报在后出现处并在首次出现处加提示，地节点 `0` 不能作引脚
+fn pin_diagnostics(statement: &Statement, subckt: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<String, Range> = HashMap::new();
+    for pin in statement.subckt_pins() {
+        if pin.text == "0" {
+            diagnostics.push(Diagnostic {
+                range: pin.range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("spice".to_string()),
+                message: format!("Ground node `0` cannot be a pin of subcircuit `{}`", subckt),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(&first) = seen.get(&pin.text.to_uppercase()) else {
+            seen.insert(pin.text.to_uppercase(), pin.range);
+            continue;
+        };
+        diagnostics.push(Diagnostic {
+            range: pin.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("spice".to_string()),
+            message: format!("Duplicate pin `{}` in subcircuit `{}`", pin.text, subckt),
+            ..Default::default()
+        });
+        diagnostics.push(Diagnostic {
+            range: first,
+            severity: Some(DiagnosticSeverity::HINT),
+            source: Some("spice".to_string()),
+            message: format!("Pin `{}` first listed here", pin.text),
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+/// 元件语句中可能是模型名的 token 下标，最后一个是报错位置；模型可选的元件（R/C/L 等）为空
+///
+/// `Q<name> <c> <b> <e> [substrate] <model> [area]` 的衬底节点可省略：
+/// 第 6 个 token 存在且不是数值时它才是模型名，否则模型是第 5 个 token。
+fn model_token_candidates(texts: &[&str]) -> Vec<usize> {
+    let nodes = node_token_range(texts);
+    let is_number = |t: &str| t.starts_with(|c: char| c.is_ascii_digit() || c == '.');
+    let candidates = match texts[0].chars().next().map(|c| c.to_ascii_uppercase()) {
+        Some('B' | 'D' | 'J' | 'M' | 'S' | 'Z') => vec![nodes.end],
+        // 控制电流的电压源之后才是模型
+        Some('W') => vec![nodes.end + 1],
+        Some('Q') if texts.get(nodes.end + 1).is_some_and(|t| !is_number(t)) => {
+            vec![nodes.end, nodes.end + 1]
+        }
+        Some('Q') => vec![nodes.end],
+        _ => vec![],
+    };
+    candidates.into_iter().filter(|&i| i < texts.len()).collect()
+}
+
+/// 半导体元件可引用的模型类型，其余元件不检查
+///
+/// `AKO:` 派生模型的类型取决于其基础模型，不检查。
+fn expected_model_types(component: &str) -> Option<&'static [&'static str]> {
+    match component.chars().next()?.to_ascii_uppercase() {
+        'D' => Some(&["D"]),
+        'J' => Some(&["NJF", "PJF"]),
+        'M' => Some(&["NMOS", "PMOS"]),
+        'Q' => Some(&["NPN", "PNP", "LPNP"]),
+        _ => None,
+    }
+}
+
+/// 节点的一次出现；接在 `X` 调用上时记下子电路名与引脚序号
+struct NodeUse {
+    range: Range,
+    pin_of: Option<(String, usize)>,
+}
+
+/// 将解析错误转换为诊断，范围从出错位置覆盖到所在单词末尾
+///
+/// 解析器给出的列是行内字节偏移，LSP 的列是 UTF-16 码元，需要转换，
+/// 否则中文、emoji 之后的错误会标错位置。
+fn parse_error_diagnostic(
+    source: &str,
+    position: Option<(usize, usize)>,
+    reason: String,
+) -> Diagnostic {
+    let (line, col) = position.unwrap_or((0, 0));
+
+    let line_text = source.lines().nth(line).unwrap_or("");
+    let start = utf16_column(line_text, col);
+    let end = match extract_word(line_text, col) {
+        Some((_, word_end)) => utf16_column(line_text, word_end),
+        None => start + 1, // 默认长度 1
+    };
+
+    Diagnostic {
+        range: Range {
+            start: Position::new(line as u32, start),
+            end: Position::new(line as u32, end),
+        },
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("spice".to_string()),
+        message: reason,
+        ..Default::default()
     }
 }
 
+/// 出错位置所在单词的字节范围（左闭右开），`location` 为行内字节偏移
+fn extract_word(line_text: &str, location: usize) -> Option<(usize, usize)> {
+    // 检查location是否在有效范围内
+    if location >= line_text.len() || !line_text.is_char_boundary(location) {
+        return None;
+    }
+    if line_text[location..].starts_with(' ') {
+        return None;
+    }
+
+    let start = line_text[..location].rfind(' ').map_or(0, |i| i + 1);
+    let end = line_text[location..]
+        .find(' ')
+        .map_or(line_text.len(), |i| location + i);
+
+    //左闭右开
+    Some((start, end))
+}
+
 
 /// 满足任一条件即全量：
 /// 1) 有任意一个 change 无 range（全量）
@@ -283,6 +607,239 @@ fn incremental_change(text: &mut String, range: &Range, new_text: &str) -> (usiz
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_duplicate_component_name() {
+        let source = "divider\nV1 1 0 DC 5\nR1 1 2 1k\nR1 2 0 2k\n.END\n";
+        let diags = analysis_diagnostics(source);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diags[0].range, Range::new(Position::new(3, 0), Position::new(3, 2)));
+        assert!(diags[0].message.contains("R1"));
+    }
+
+    #[test]
+    fn test_title_line_not_checked() {
+        // 标题以元件字母开头，其中的单词不是节点
+        let source = "RC filter test\nV1 IN 0 DC 5\nR1 IN 0 1k\n.END\n";
+        assert!(analysis_diagnostics(source).is_empty());
+    }
+
+    #[test]
+    fn test_floating_node_and_scopes() {
+        let source = "\
+.SUBCKT DIV IN OUT
+R1 IN OUT 1k
+R2 OUT 0 1k
+.ENDS
+R1 1 0 1k
+R2 1 n5 1k
+";
+        // 子电路内的 R1 与顶层 R1 不冲突；端口 IN 只用一次也不算悬空
+        let diags = analysis_diagnostics(source);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diags[0].range.start, Position::new(5, 5));
+        assert!(diags[0].message.contains("n5"));
+    }
+
+    #[test]
+    fn test_undefined_models() {
+        let source = "\
+.MODEL DMOD D (IS=1e-14)
+V1 A 0 DC 5
+D1 A B DMOD
+Q1 B A 0 QMISSING
+Q2 B A 0 SUB QLOCAL 2
+.SUBCKT AMP IN OUT
+.MODEL QLOCAL NPN (BF=80)
+Q3 IN OUT 0 QLOCAL
+D2 IN OUT dmod
+.ENDS
+Q4 B A 0 QLOCAL
+";
+        let messages: Vec<String> = analysis_diagnostics(source)
+            .into_iter()
+            .filter(|d| d.message.starts_with("Undefined model"))
+            .map(|d| d.message)
+            .collect();
+        // 子电路内的模型对外不可见，外层模型对子电路可见
+        assert_eq!(
+            messages,
+            vec![
+                "Undefined model `QMISSING`",
+                "Undefined model `QLOCAL`",
+                "Undefined model `QLOCAL`",
+            ]
+        );
+
+        // 引入库文件时不报未定义模型
+        let with_lib = format!(".LIB \"bipolar.lib\"\n{}", source);
+        assert!(
+            analysis_diagnostics(&with_lib)
+                .iter()
+                .all(|d| !d.message.starts_with("Undefined model"))
+        );
+    }
+
+    #[test]
+    fn test_model_type_mismatch() {
+        let source = "\
+* model types
+.MODEL DMOD D (IS=1e-14)
+.MODEL QN NPN (BF=100)
+V1 A 0 DC 5
+Q1 A B 0 DMOD
+D1 B 0 QN
+Q2 A B 0 QN
+D2 B 0 DMOD
+.END
+";
+        let diags = analysis_diagnostics(source);
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diags[0].range, Range::new(Position::new(4, 9), Position::new(4, 13)));
+        assert_eq!(
+            diags[0].message,
+            "`Q1` expects a NPN/PNP/LPNP model, but `DMOD` is a D model"
+        );
+        assert_eq!(diags[1].range.start, Position::new(5, 7));
+        assert!(diags[1].message.contains("NPN model"));
+    }
+
+    #[test]
+    fn test_end_statement() {
+        let messages = |source: &str| -> Vec<(String, u32)> {
+            analysis_diagnostics(source)
+                .into_iter()
+                .map(|d| (d.message, d.range.start.line))
+                .collect()
+        };
+        assert!(messages("* one\nR1 A 0 1k\nR2 A 0 1k\n.END\n").is_empty());
+        // 缺少 `.END` 时报在文末；库文件没有标题，不要求 `.END`
+        assert_eq!(
+            messages("* zero\nR1 A 0 1k\nR2 A 0 1k\n"),
+            vec![("Missing `.END`".to_string(), 3)]
+        );
+        assert!(messages(".MODEL DMOD D\n").is_empty());
+        assert_eq!(
+            messages("* two\nR1 A 0 1k\nR2 A 0 1k\n.END\n.end\n"),
+            vec![("Duplicate `.END`".to_string(), 4)]
+        );
+        assert_eq!(
+            messages("* mid\nR1 A 0 1k\n.END\nR2 A 0 1k\n"),
+            vec![("Statements after `.END` are ignored".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_subckt_pin() {
+        let source = ".SUBCKT AMP IN OUT in\nR1 IN OUT 1k\n.ENDS\n";
+        let diags = analysis_diagnostics(source);
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diags[0].range, Range::new(Position::new(0, 19), Position::new(0, 21)));
+        assert_eq!(diags[0].message, "Duplicate pin `in` in subcircuit `AMP`");
+        // 首次出现处的提示
+        assert_eq!(diags[1].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diags[1].range.start, Position::new(0, 12));
+    }
+
+    #[test]
+    fn test_ground_subckt_pin() {
+        let source = ".SUBCKT AMP IN, 0\n+ OUT\nR1 IN OUT 1k\n.ENDS\n";
+        let diags = analysis_diagnostics(source);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diags[0].range, Range::new(Position::new(0, 16), Position::new(0, 17)));
+        assert!(diags[0].message.contains("Ground node `0`"));
+    }
+
+    #[test]
+    fn test_node_through_subckt_pin_not_floating() {
+        let source = "\
+* divider instance
+V1 IN 0 DC 5
+X1 IN OUT DIV
+.SUBCKT DIV A B
+R1 A B 1k
+R2 B 0 1k
+.ENDS
+X2 IN N9 LIBSUB
+.END
+";
+        // OUT 经 X1 的第二个引脚连到 R1、R2；LIBSUB 不在本文件中，N9 不报
+        assert!(analysis_diagnostics(source).is_empty());
+
+        // 接到子电路中未使用的端口上仍然悬空
+        let unused_pin = "* half\nV1 IN 0 DC 5\nX1 IN OUT HALF\n.SUBCKT HALF A B\nR1 A 0 1k\n.ENDS\n.END\n";
+        let diags = analysis_diagnostics(unused_pin);
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.contains("OUT"));
+    }
+
+    #[test]
+    fn test_nodes_on_continuation_lines() {
+        // S 只在 M1 的续行上出现过一次，不合并续行会被误报
+        let source = "\
+.MODEL NMOD NMOS
+V1 IN 0 DC 5
+M1 OUT IN
+* 续行前可以有注释
++ S 0 NMOD
+R1 S OUT 1k
+";
+        assert!(analysis_diagnostics(source).is_empty());
+
+        // `+` 紧贴单词时，范围从 `+` 之后开始
+        let diags = analysis_diagnostics("* tight\nV1 IN 0 DC 5\nR2 IN\n+N7 1k\n.END\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range, Range::new(Position::new(3, 1), Position::new(3, 3)));
+    }
+
+    #[test]
+    fn test_parse_error_diagnostic_shape() {
+        let source = "R1 1 2 1k\nC1 2 0 1uX\n";
+        let diag = parse_error_diagnostic(source, Some((1, 7)), "bad value".to_string());
+        assert_eq!(diag.range.start, Position::new(1, 7));
+        assert_eq!(diag.range.end, Position::new(1, 10));
+        assert_eq!(diag.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diag.source.as_deref(), Some("spice"));
+        assert_eq!(diag.message, "bad value");
+
+        // 无位置信息时落在文档开头
+        let diag = parse_error_diagnostic(source, None, "eof".to_string());
+        assert_eq!(diag.range.start, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_parse_error_after_cjk() {
+        // "V你好" 占 7 字节、3 个 UTF-16 码元；错误单词 "5X" 位于字节 15
+        let source = "* 测试\nV你好 1 0 DC 5X\n";
+        let diag = parse_error_diagnostic(source, Some((1, 15)), "bad value".to_string());
+        assert_eq!(diag.range.start, Position::new(1, 11));
+        assert_eq!(diag.range.end, Position::new(1, 13));
+    }
+
+    #[test]
+    fn test_parse_error_after_astral() {
+        // 👍 占 4 字节、2 个 UTF-16 码元；按 char 计会少算一列
+        let source = "R👍 1 2 1kX\n";
+        let diag = parse_error_diagnostic(source, Some((0, 10)), "bad value".to_string());
+        assert_eq!(diag.range.start, Position::new(0, 8));
+        assert_eq!(diag.range.end, Position::new(0, 11));
+        assert_eq!(extract_word(source.lines().next().unwrap(), 2), None); // 👍 中间
+    }
+
+    #[test]
+    fn test_extract_word_byte_range() {
+        let line = "C你 2 0 1uF";
+        assert_eq!(extract_word(line, 0), Some((0, 4)));
+        assert_eq!(extract_word(line, 2), None); // 不在字符边界
+        assert_eq!(extract_word(line, 4), None); // 空格
+        assert_eq!(extract_word(line, 9), Some((9, 12)));
+        assert_eq!(extract_word(line, 12), None); // 行尾
+    }
+
     #[test]
     fn test_single_line_insert() {
         let mut text = String::from("R1 1 2 1k\nC1 2 3 1uF");
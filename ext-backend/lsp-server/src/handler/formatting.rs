@@ -0,0 +1,294 @@
+use crate::netlist::is_title_line;
+use crate::position::utf16_column;
+use crate::state::SharedServerState;
+use crate::symbol_info::terminal::node_token_range;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+/// 超过该宽度（字符数）的行用 `+` 续行拆分
+const MAX_LINE_WIDTH: usize = 80;
+
+pub async fn on_formatting(
+    state: SharedServerState,
+    params: DocumentFormattingParams,
+) -> Result<Option<Vec<TextEdit>>, tower_lsp::jsonrpc::Error> {
+    let s = state.lock().await;
+    let Some(doc) = s.documents.get(&params.text_document.uri) else {
+        return Ok(None);
+    };
+    Ok(Some(format_edits(&doc.text)))
+}
+
+/// 一行切词后的结果
+struct LineTokens<'a> {
+    tokens: Vec<&'a str>,
+    /// `;` 起的行内注释
+    comment: Option<&'a str>,
+    /// 所属作用域块：顶层为 0，每个 `.SUBCKT` 一个新块
+    block: usize,
+    /// 参与列对齐的 token 个数（元件名与节点），命令行为 0
+    aligned: usize,
+}
+
+/// 逐行比较格式化结果，只为有变化的行生成替换
+fn format_edits(text: &str) -> Vec<TextEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    lines
+        .iter()
+        .zip(format_lines(&lines, newline))
+        .enumerate()
+        .filter(|(_, (old, new))| **old != new.as_str())
+        .map(|(i, (old, new))| TextEdit {
+            range: Range::new(
+                Position::new(i as u32, 0),
+                Position::new(i as u32, utf16_column(old, old.len())),
+            ),
+            new_text: new,
+        })
+        .collect()
+}
+
+/// 格式化各行，结果与输入行一一对应（被拆分的行以文档自身的换行符 `newline` 连接续行）
+///
+/// - 标题行、`*` 注释行与空白行原样保留，`;` 行内注释跟在代码后
+/// - token 之间单个空格，命令关键字大写
+/// - 同一子电路（或顶层）内元件名与节点列对齐
+/// - 超过 [`MAX_LINE_WIDTH`] 的行拆成 `+` 续行
+fn format_lines(lines: &[&str], newline: &str) -> Vec<String> {
+    let mut parsed = Vec::with_capacity(lines.len());
+    let mut blocks = vec![0];
+    let mut next_block = 1;
+    for (index, line) in lines.iter().enumerate() {
+        let (tokens, comment) = split_code(line);
+        let first = tokens.first().map(|t| t.to_uppercase());
+        let mut block = *blocks.last().unwrap_or(&0);
+        match first.as_deref() {
+            Some(".SUBCKT") => {
+                blocks.push(next_block);
+                next_block += 1;
+            }
+            Some(".ENDS") => {
+                blocks.pop();
+                block = *blocks.last().unwrap_or(&0);
+            }
+            _ => {}
+        }
+        let is_component = tokens
+            .first()
+            .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_alphabetic()));
+        let aligned = if is_component && !is_title_line(index, line) {
+            node_token_range(&tokens).end
+        } else {
+            0
+        };
+        parsed.push(LineTokens {
+            tokens,
+            comment,
+            block,
+            aligned,
+        });
+    }
+
+    let mut widths: HashMap<(usize, usize), usize> = HashMap::new();
+    for line in &parsed {
+        for (col, token) in line.tokens.iter().take(line.aligned).enumerate() {
+            let width = widths.entry((line.block, col)).or_default();
+            *width = (*width).max(token.chars().count());
+        }
+    }
+
+    lines
+        .iter()
+        .zip(&parsed)
+        .enumerate()
+        .map(|(index, (raw, line))| {
+            if is_title_line(index, raw) {
+                return raw.to_string();
+            }
+            if line.tokens.is_empty() || raw.trim_start().starts_with('*') {
+                return if line.comment.is_some() || raw.trim_start().starts_with('*') {
+                    raw.to_string()
+                } else {
+                    String::new()
+                };
+            }
+            let words: Vec<String> = line
+                .tokens
+                .iter()
+                .enumerate()
+                .map(|(col, token)| {
+                    if col == 0 && token.starts_with('.') {
+                        token.to_uppercase()
+                    } else if col < line.aligned && col + 1 < line.tokens.len() {
+                        format!("{:<1$}", token, widths[&(line.block, col)])
+                    } else {
+                        token.to_string()
+                    }
+                })
+                .collect();
+            wrap(words, line.comment, newline)
+        })
+        .collect()
+}
+
+/// 贪心地把单词排进不超过宽度的行，放不下时另起 `+` 续行；行内注释接在最后
+fn wrap(words: Vec<String>, comment: Option<&str>, newline: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut current = String::new();
+    // 当前行除 `+` 以外已放入的单词数，至少放一个才允许换行
+    let mut placed = 0;
+    for word in words {
+        if current.is_empty() && word == "+" {
+            current = word;
+            continue;
+        }
+        let width = current.chars().count() + 1 + word.trim_end().chars().count();
+        if placed > 0 && width > MAX_LINE_WIDTH {
+            out.push(current.trim_end().to_string());
+            current = "+".to_string();
+            placed = 0;
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+        placed += 1;
+    }
+    if let Some(comment) = comment {
+        current.push(' ');
+        current.push_str(comment);
+    }
+    out.push(current.trim_end().to_string());
+    out.join(newline)
+}
+
+/// 按空白切词；引号字符串与 `{...}` 表达式内部的空白不切分，
+/// 引号外的 `;` 之后为行内注释
+fn split_code(line: &str) -> (Vec<&str>, Option<&str>) {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    let mut depth = 0usize;
+    for (i, c) in line.char_indices() {
+        if !quoted && depth == 0 {
+            if c == ';' {
+                if let Some(st) = start.take() {
+                    tokens.push(&line[st..i]);
+                }
+                return (tokens, Some(line[i..].trim_end()));
+            }
+            if c.is_whitespace() {
+                if let Some(st) = start.take() {
+                    tokens.push(&line[st..i]);
+                }
+                continue;
+            }
+        }
+        match c {
+            '"' => quoted = !quoted,
+            '{' if !quoted => depth += 1,
+            '}' if !quoted => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(st) = start {
+        tokens.push(&line[st..]);
+    }
+    (tokens, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(text: &str) -> String {
+        let lines: Vec<&str> = text.lines().collect();
+        format_lines(&lines, "\n").join("\n")
+    }
+
+    #[test]
+    fn test_format_messy_rcl() {
+        let messy = "\
+* RCL   circuit
+v1   in 0   dc 5
+r1 in   mid 1k ; series   resistor
+c10 mid 0 1u
+l1 mid out {L0 *  2}
+.subckt  load a  b
+rload a   b 50
+.ends
+.tran  1n 100n
+.end";
+        let expected = "\
+* RCL   circuit
+v1  in  0   dc 5
+r1  in  mid 1k ; series   resistor
+c10 mid 0   1u
+l1  mid out {L0 *  2}
+.SUBCKT load a b
+rload a b 50
+.ENDS
+.TRAN 1n 100n
+.END";
+        assert_eq!(format(messy), expected);
+        // 已格式化的文本不再产生修改
+        assert!(format_edits(expected).is_empty());
+    }
+
+    #[test]
+    fn test_long_line_split_into_continuations() {
+        let line = ".MODEL QLONG NPN (IS=1e-16 BF=100 VAF=100 IKF=0.1 ISE=1e-14 NE=1.5 \
+                    BR=5 VAR=20 RB=10 RE=0.5 RC=1 CJE=1p CJC=1p TF=0.3n TR=10n) ; long";
+        let formatted = format(line);
+        let out: Vec<&str> = formatted.lines().collect();
+        assert!(out.len() > 1);
+        assert!(out[0].starts_with(".MODEL QLONG NPN"));
+        assert!(out[1..].iter().all(|l| l.starts_with("+ ")));
+        assert!(
+            out[..out.len() - 1]
+                .iter()
+                .all(|l| l.chars().count() <= MAX_LINE_WIDTH)
+        );
+        assert!(out.last().unwrap().ends_with("; long"));
+
+        // 拆分前后的 token 一致
+        let joined: Vec<&str> = out
+            .iter()
+            .flat_map(|l| l.split_whitespace())
+            .filter(|t| *t != "+")
+            .collect();
+        let original: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(joined, original);
+    }
+
+    #[test]
+    fn test_edit_replaces_whole_line() {
+        let edits = format_edits("R1 1 2 1k\nr2  2 0 1k\n");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 10))
+        );
+        assert_eq!(edits[0].new_text, "r2 2 0 1k");
+    }
+
+    #[test]
+    fn test_title_line_kept_verbatim() {
+        let messy = "RC   filter  test\nR1  in out 1k\nC10 out 0 1u";
+        let expected = "RC   filter  test\nR1  in  out 1k\nC10 out 0   1u";
+        assert_eq!(format(messy), expected);
+    }
+
+    #[test]
+    fn test_continuations_use_document_line_ending() {
+        let text = format!("* crlf\r\n.PARAM {}\r\n", "LONGNAME=1 ".repeat(10).trim_end());
+        let edits = format_edits(&text);
+        assert_eq!(edits.len(), 1);
+        assert!(edits[0].new_text.contains("\r\n+ "));
+        assert!(!edits[0].new_text.replace("\r\n", "").contains('\n'));
+    }
+}
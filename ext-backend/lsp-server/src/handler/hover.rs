@@ -0,0 +1,112 @@
+use crate::netlist::line_words;
+use crate::position::{byte_column, utf16_column};
+use crate::state::SharedServerState;
+use crate::symbol_info::terminal::{node_token_range, terminal_roles};
+use tower_lsp::lsp_types::*;
+
+pub async fn on_hover(
+    state: SharedServerState,
+    params: HoverParams,
+) -> Result<Option<Hover>, tower_lsp::jsonrpc::Error> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let s = state.lock().await;
+    let Some(doc) = s.documents.get(&uri) else {
+        return Ok(None);
+    };
+    let Some((range, role, component)) = node_role_at(&doc.text, position) else {
+        return Ok(None);
+    };
+
+    let contents = if s.client_support.hover_markdown {
+        MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("**{}** of `{}`", role, component),
+        }
+    } else {
+        MarkupContent {
+            kind: MarkupKind::PlainText,
+            value: format!("{} of {}", role, component),
+        }
+    };
+    Ok(Some(Hover {
+        contents: HoverContents::Markup(contents),
+        range: Some(range),
+    }))
+}
+
+/// 光标所在节点在元件中的端子含义，如 `Q1 C B E QMOD` 中 `B` 为 base node
+///
+/// 返回节点的范围、端子含义与元件名；光标不在元件的节点字段上时返回 `None`。
+fn node_role_at(text: &str, position: Position) -> Option<(Range, String, String)> {
+    let line_text = text.lines().nth(position.line as usize)?;
+    let cursor = byte_column(line_text, position.character);
+
+    let words = line_words(line_text);
+    let tokens: Vec<&str> = words.iter().map(|&(st, ed)| &line_text[st..ed]).collect();
+    let component = tokens
+        .first()
+        .filter(|t| t.starts_with(|c: char| c.is_ascii_alphabetic()))?;
+    let letter = component.chars().next()?;
+
+    let index = words
+        .iter()
+        .position(|&(st, ed)| st <= cursor && cursor <= ed)?;
+    let nodes = node_token_range(&tokens);
+    if !nodes.contains(&index) {
+        return None;
+    }
+
+    let role = if letter.eq_ignore_ascii_case(&'X') {
+        // 子电路名紧跟在最后一个节点之后
+        format!("pin {} of subcircuit `{}`", index, tokens[nodes.end])
+    } else {
+        terminal_roles(letter).get(index - 1)?.to_string()
+    };
+
+    let (st, ed) = words[index];
+    let line = position.line;
+    let range = Range::new(
+        Position::new(line, utf16_column(line_text, st)),
+        Position::new(line, utf16_column(line_text, ed)),
+    );
+    Some((range, role, component.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hover_bjt_base() {
+        let text = "V1 VCC 0 DC 5\nQ1 VCC BASE 0 QNPN\n";
+        let (range, role, component) = node_role_at(text, Position::new(1, 8)).unwrap();
+        assert_eq!(role, "base node");
+        assert_eq!(component, "Q1");
+        assert_eq!(range, Range::new(Position::new(1, 7), Position::new(1, 11)));
+    }
+
+    #[test]
+    fn test_hover_mosfet_and_subckt_pins() {
+        let text = "M1 D G S B NMOD L=1u\nX1 IN OUT FILTER PARAMS: GAIN=2\n";
+        assert_eq!(
+            node_role_at(text, Position::new(0, 9)).unwrap().1,
+            "bulk/substrate node"
+        );
+        assert_eq!(
+            node_role_at(text, Position::new(1, 4)).unwrap().1,
+            "pin 1 of subcircuit `FILTER`"
+        );
+    }
+
+    #[test]
+    fn test_no_hover_outside_nodes() {
+        let text = "Q1 C B E QNPN\n.TRAN 1n 10n\n* Q1 C B E\n";
+        // 元件名、模型名、命令行与注释行都不是节点
+        assert!(node_role_at(text, Position::new(0, 0)).is_none());
+        assert!(node_role_at(text, Position::new(0, 10)).is_none());
+        assert!(node_role_at(text, Position::new(1, 7)).is_none());
+        assert!(node_role_at(text, Position::new(2, 5)).is_none());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod completion;
+pub mod definition;
 pub mod diagnostics;
+pub mod formatting;
+pub mod hover;
+pub mod semantic_tokens;
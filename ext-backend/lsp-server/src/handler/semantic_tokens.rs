@@ -0,0 +1,391 @@
+use crate::netlist::is_title_line;
+use crate::position::utf16_column;
+use crate::state::SharedServerState;
+use crate::symbol_info::terminal::node_token_range;
+use tower_lsp::lsp_types::*;
+
+/// 语义 token 类型表，下标即 `SemanticToken::token_type`
+pub const TOKEN_TYPES: [SemanticTokenType; 8] = [
+    SemanticTokenType::COMMENT,   // 标题行、`*` 注释行与 `;` 行内注释
+    SemanticTokenType::KEYWORD,   // `.TRAN` 等命令、`PARAMS:` 分节、`DC`/`SIN` 等源关键字
+    SemanticTokenType::FUNCTION,  // 元件名，如 `R1`
+    SemanticTokenType::VARIABLE,  // 节点
+    SemanticTokenType::PARAMETER, // `name=value` 中的 name
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::OPERATOR, // 续行符 `+`
+];
+
+const COMMENT: u32 = 0;
+const KEYWORD: u32 = 1;
+const FUNCTION: u32 = 2;
+const VARIABLE: u32 = 3;
+const PARAMETER: u32 = 4;
+const NUMBER: u32 = 5;
+const STRING: u32 = 6;
+const OPERATOR: u32 = 7;
+
+/// 源与扫描中出现的关键字
+const SOURCE_KEYWORDS: [&str; 12] = [
+    "AC", "DC", "DEC", "EXP", "LIN", "LIST", "OCT", "PULSE", "PWL", "SFFM", "SIN", "TEMP",
+];
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![],
+    }
+}
+
+pub async fn on_semantic_tokens_full(
+    state: SharedServerState,
+    params: SemanticTokensParams,
+) -> Result<Option<SemanticTokensResult>, tower_lsp::jsonrpc::Error> {
+    let uri = params.text_document.uri;
+    let mut s = state.lock().await;
+    let Some(doc) = s.documents.get_mut(&uri) else {
+        return Ok(None);
+    };
+
+    let tokens = SemanticTokens {
+        result_id: Some(doc.version.to_string()),
+        data: semantic_tokens(&doc.text),
+    };
+    doc.semantic_tokens = Some(tokens.clone());
+    Ok(Some(SemanticTokensResult::Tokens(tokens)))
+}
+
+/// 与上次返回的 token 集合（按 result_id 即文档版本对应）比较，只返回变化部分；
+/// 对不上上次结果时退回全量
+pub async fn on_semantic_tokens_full_delta(
+    state: SharedServerState,
+    params: SemanticTokensDeltaParams,
+) -> Result<Option<SemanticTokensFullDeltaResult>, tower_lsp::jsonrpc::Error> {
+    let uri = params.text_document.uri;
+    let mut s = state.lock().await;
+    let Some(doc) = s.documents.get_mut(&uri) else {
+        return Ok(None);
+    };
+
+    let tokens = SemanticTokens {
+        result_id: Some(doc.version.to_string()),
+        data: semantic_tokens(&doc.text),
+    };
+    let previous = doc.semantic_tokens.replace(tokens.clone());
+
+    let result = match previous {
+        Some(prev) if prev.result_id.as_deref() == Some(params.previous_result_id.as_str()) => {
+            SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: tokens.result_id,
+                edits: semantic_tokens_edits(&prev.data, &tokens.data),
+            })
+        }
+        _ => SemanticTokensFullDeltaResult::Tokens(tokens),
+    };
+    Ok(Some(result))
+}
+
+/// 只计算请求范围内各行的 token，大文件滚动时不必整篇分类
+pub async fn on_semantic_tokens_range(
+    state: SharedServerState,
+    params: SemanticTokensRangeParams,
+) -> Result<Option<SemanticTokensRangeResult>, tower_lsp::jsonrpc::Error> {
+    let s = state.lock().await;
+    let Some(doc) = s.documents.get(&params.text_document.uri) else {
+        return Ok(None);
+    };
+
+    Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: semantic_tokens_in_range(&doc.text, params.range),
+    })))
+}
+
+/// 整个文档的语义 token（LSP 相对编码）
+pub fn semantic_tokens(text: &str) -> Vec<SemanticToken> {
+    encode(text.lines().enumerate())
+}
+
+/// `range` 覆盖的各行（含首尾行）的语义 token；首个 token 的 `delta_line` 相对文档开头
+pub fn semantic_tokens_in_range(text: &str, range: Range) -> Vec<SemanticToken> {
+    let first = range.start.line as usize;
+    let count = (range.end.line as usize + 1).saturating_sub(first);
+    encode(text.lines().enumerate().skip(first).take(count))
+}
+
+/// 把 `(行号, 行文本)` 序列逐行分类并按 LSP 相对格式编码
+fn encode<'a>(lines: impl Iterator<Item = (usize, &'a str)>) -> Vec<SemanticToken> {
+    let mut data = Vec::new();
+    let mut prev_line = 0;
+    let mut prev_start = 0;
+    for (line_idx, line) in lines {
+        // 标题行是任意文本，整行按注释着色
+        let tokens = if is_title_line(line_idx, line) {
+            whole_line(line, COMMENT)
+        } else {
+            classify_line(line)
+        };
+        for (start, length, token_type) in tokens {
+            let line_idx = line_idx as u32;
+            let delta_line = line_idx - prev_line;
+            let delta_start = if delta_line == 0 {
+                start - prev_start
+            } else {
+                start
+            };
+            data.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type,
+                token_modifiers_bitset: 0,
+            });
+            prev_line = line_idx;
+            prev_start = start;
+        }
+    }
+    data
+}
+
+/// 计算新旧 token 序列的差异：去掉公共前缀和后缀，中间部分作为一次替换
+///
+/// `start`/`delete_count` 以展开后的整数数组计，每个 token 占 5 个整数。
+fn semantic_tokens_edits(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let deleted = old.len() - prefix - suffix;
+    let inserted = &new[prefix..new.len() - suffix];
+    if deleted == 0 && inserted.is_empty() {
+        return vec![];
+    }
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: (deleted * 5) as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+/// 对单行分类，返回 `(UTF-16 起始列, UTF-16 长度, token 类型)`
+fn classify_line(line: &str) -> Vec<(u32, u32, u32)> {
+    let span = |start: usize, end: usize, token_type: u32| {
+        let col = utf16_column(line, start);
+        (col, utf16_column(line, end) - col, token_type)
+    };
+
+    if line.trim_start().starts_with('*') {
+        return whole_line(line, COMMENT);
+    }
+
+    // 先切词：空白与 `,()={}` 为分隔符，引号内为字符串，`;` 之后为注释
+    let mut words: Vec<(usize, usize)> = Vec::new();
+    let mut strings: Vec<(usize, usize)> = Vec::new();
+    let mut comment: Option<usize> = None;
+    let mut word_start: Option<usize> = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let is_separator = c.is_whitespace() || matches!(c, ',' | '(' | ')' | '=' | '{' | '}');
+        if (is_separator || c == ';' || c == '"')
+            && let Some(st) = word_start.take()
+        {
+            words.push((st, i));
+        }
+        if c == ';' {
+            comment = Some(i);
+            break;
+        }
+        if c == '"' {
+            let mut end = line.len();
+            for (j, d) in chars.by_ref() {
+                if d == '"' {
+                    end = j + 1;
+                    break;
+                }
+            }
+            strings.push((i, end));
+            continue;
+        }
+        if !is_separator && word_start.is_none() {
+            word_start = Some(i);
+        }
+    }
+    if let Some(st) = word_start {
+        words.push((st, comment.unwrap_or(line.len())));
+    }
+
+    let texts: Vec<&str> = words.iter().map(|&(st, ed)| &line[st..ed]).collect();
+    let is_component = texts
+        .first()
+        .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_alphabetic()));
+    let nodes = if is_component {
+        node_token_range(&texts)
+    } else {
+        0..0
+    };
+
+    let mut out: Vec<(u32, u32, u32)> = Vec::new();
+    for (idx, &(st, ed)) in words.iter().enumerate() {
+        let text = texts[idx];
+        let followed_by_eq = line[ed..].trim_start().starts_with('=');
+        let token_type = if idx == 0 && text.starts_with('.') {
+            KEYWORD
+        } else if idx == 0 && text == "+" {
+            OPERATOR
+        } else if idx == 0 && is_component {
+            FUNCTION
+        } else if nodes.contains(&idx) {
+            VARIABLE
+        } else if followed_by_eq {
+            PARAMETER
+        } else if text.ends_with(':') || SOURCE_KEYWORDS.contains(&text.to_uppercase().as_str()) {
+            KEYWORD
+        } else if is_number(text) {
+            NUMBER
+        } else {
+            continue;
+        };
+        out.push(span(st, ed, token_type));
+    }
+    out.extend(strings.iter().map(|&(st, ed)| span(st, ed, STRING)));
+    if let Some(st) = comment {
+        out.push(span(st, line.len(), COMMENT));
+    }
+    out.sort_by_key(|t| t.0);
+    out
+}
+
+/// 去掉行首空白后整行作为一个 token，空白行没有 token
+fn whole_line(line: &str, token_type: u32) -> Vec<(u32, u32, u32)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return vec![];
+    }
+    let start = utf16_column(line, line.len() - line.trim_start().len());
+    vec![(start, trimmed.encode_utf16().count() as u32, token_type)]
+}
+
+/// 数值（可带正负号、科学计数法与单位后缀，如 `-1.5e-6`、`10k`、`.5uF`）
+fn is_number(text: &str) -> bool {
+    let body = text.trim_start_matches(['+', '-']);
+    let mut chars = body.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('.') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &[SemanticToken], edits: &[SemanticTokensEdit]) -> Vec<SemanticToken> {
+        let mut result = old.to_vec();
+        for edit in edits {
+            let start = edit.start as usize / 5;
+            let end = start + edit.delete_count as usize / 5;
+            result.splice(start..end, edit.data.clone().unwrap_or_default());
+        }
+        result
+    }
+
+    #[test]
+    fn test_classify_component_line() {
+        let tokens = classify_line("R1 1 2 1k TC=0.01 ; load");
+        let types: Vec<u32> = tokens.iter().map(|t| t.2).collect();
+        assert_eq!(
+            types,
+            vec![
+                FUNCTION, VARIABLE, VARIABLE, NUMBER, PARAMETER, NUMBER, COMMENT
+            ]
+        );
+        // 节点 `1` 按位置识别为节点而非数值
+        assert_eq!(tokens[1], (3, 1, VARIABLE));
+    }
+
+    #[test]
+    fn test_classify_comment_and_command() {
+        assert_eq!(classify_line("* 注释"), vec![(0, 4, COMMENT)]);
+        let tokens = classify_line(".TRAN 1ns 100ns");
+        assert_eq!(tokens[0], (0, 5, KEYWORD));
+        assert_eq!(tokens[1].2, NUMBER);
+        let tokens = classify_line("X1 IN OUT FILTER PARAMS: GAIN=2");
+        let types: Vec<u32> = tokens.iter().map(|t| t.2).collect();
+        assert_eq!(
+            types,
+            vec![FUNCTION, VARIABLE, VARIABLE, KEYWORD, PARAMETER, NUMBER]
+        );
+    }
+
+    #[test]
+    fn test_title_line_is_comment() {
+        let tokens = absolute(&semantic_tokens("R1 filter test \nR1 A 0 1k\n"));
+        assert_eq!(tokens[0], (0, 0, 14, COMMENT));
+        assert_eq!(tokens[1], (1, 0, 2, FUNCTION));
+        // 库文件的首行是命令，照常分类
+        assert_eq!(absolute(&semantic_tokens(".MODEL D1N D\n"))[0].3, KEYWORD);
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        // 中文注释前的 token 不受影响，注释的起始列按 UTF-16 计
+        let tokens = classify_line("V1 节点 0 DC 5 ; 😀");
+        assert_eq!(tokens[1], (3, 2, VARIABLE));
+        assert_eq!(tokens.last(), Some(&(13, 4, COMMENT)));
+    }
+
+    /// 解码为 `(行, 列, 长度, 类型)` 的绝对位置
+    fn absolute(tokens: &[SemanticToken]) -> Vec<(u32, u32, u32, u32)> {
+        let (mut line, mut col) = (0, 0);
+        tokens
+            .iter()
+            .map(|t| {
+                line += t.delta_line;
+                col = if t.delta_line == 0 {
+                    col + t.delta_start
+                } else {
+                    t.delta_start
+                };
+                (line, col, t.length, t.token_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_range_matches_slice_of_full() {
+        let text = "* lib\nR1 1 2 1k\nC1 2 0 1uF\n+ IC=0\n.TRAN 1ns 100ns\n";
+        let full = absolute(&semantic_tokens(text));
+        let range = Range::new(Position::new(2, 0), Position::new(3, 6));
+        let ranged = absolute(&semantic_tokens_in_range(text, range));
+
+        let expected: Vec<_> = full
+            .into_iter()
+            .filter(|t| (2..=3).contains(&t.0))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(ranged, expected);
+        // 首个 token 从第 2 行开始编码
+        assert_eq!(semantic_tokens_in_range(text, range)[0].delta_line, 2);
+    }
+
+    #[test]
+    fn test_delta_after_single_line_edit() {
+        let before = "* rc\nR1 1 2 1k\nC1 2 0 1uF\n.TRAN 1ns 100ns\n";
+        let after = "* rc\nR1 1 2 1k\nC1 2 0 10uF IC=0\n.TRAN 1ns 100ns\n";
+        let old = semantic_tokens(before);
+        let new = semantic_tokens(after);
+
+        let edits = semantic_tokens_edits(&old, &new);
+        assert_eq!(edits.len(), 1);
+        // 前 8 个 token（标题、`R1` 行 4 个与 `C1 2 0`）不变
+        assert_eq!(edits[0].start, 8 * 5);
+        assert_eq!(apply(&old, &edits), new);
+
+        assert!(semantic_tokens_edits(&new, &new).is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use tower_lsp::{LspService, Server};
 mod handler;
+mod netlist;
+mod position;
 mod server;
 mod state;
 mod symbol_info;
@@ -0,0 +1,182 @@
+//! 文本层面的网表扫描：切词、合并 `+` 续行、跟踪 `.SUBCKT` 作用域
+//!
+//! 诊断、跳转定义与符号表都从这里取语句，三者对同一段源码的理解保持一致。
+
+use crate::position::utf16_column;
+use tower_lsp::lsp_types::{Position, Range};
+
+/// 语句中的一个单词
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Word<'a> {
+    pub text: &'a str,
+    /// 所在行号
+    pub line: usize,
+    /// 在全文中的字节偏移
+    pub offset: usize,
+    /// LSP 范围（UTF-16 列）
+    pub range: Range,
+}
+
+/// 一条语句，`+` 续行已并入
+#[derive(Debug, Clone)]
+pub struct Statement<'a> {
+    pub words: Vec<Word<'a>>,
+    /// 所在子电路的嵌套路径（大写），顶层为空；
+    /// `.SUBCKT` 语句属于外层，`.ENDS` 语句属于它结束的子电路
+    pub scope: Vec<String>,
+}
+
+impl<'a> Statement<'a> {
+    /// 首个单词的大写形式，即元件名或命令关键字
+    pub fn keyword(&self) -> String {
+        self.words[0].text.to_uppercase()
+    }
+
+    pub fn texts(&self) -> Vec<&'a str> {
+        self.words.iter().map(|w| w.text).collect()
+    }
+
+    /// `.SUBCKT <name> <pin>...` 的引脚，到 `PARAMS:` 等分节关键字为止
+    pub fn subckt_pins(&self) -> &[Word<'a>] {
+        let pins = self.words.get(2..).unwrap_or_default();
+        let end = pins
+            .iter()
+            .position(|w| w.text.ends_with(':'))
+            .unwrap_or(pins.len());
+        &pins[..end]
+    }
+}
+
+/// 第 `index` 行是否为电路标题
+///
+/// SPICE 把第一行整行当作标题；以 `.` 开头的命令行除外，被 `.INC` 引入的库文件没有标题。
+pub fn is_title_line(index: usize, line: &str) -> bool {
+    index == 0 && !line.trim_start().starts_with('.')
+}
+
+/// 把源码切成语句：跳过标题行，`+` 续行并入上一条语句，并记下每条语句所在的子电路
+pub fn statements(source: &str) -> Vec<Statement<'_>> {
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut scope: Vec<String> = Vec::new();
+    let mut line_start = 0;
+    for (line_idx, line) in source.split('\n').enumerate() {
+        let offset = line_start;
+        line_start += line.len() + 1;
+        if is_title_line(line_idx, line) {
+            continue;
+        }
+        let mut words: Vec<Word> = line_words(line)
+            .into_iter()
+            .map(|(st, ed)| Word {
+                text: &line[st..ed],
+                line: line_idx,
+                offset: offset + st,
+                range: Range::new(
+                    Position::new(line_idx as u32, utf16_column(line, st)),
+                    Position::new(line_idx as u32, utf16_column(line, ed)),
+                ),
+            })
+            .collect();
+        let Some(&first) = words.first() else {
+            continue;
+        };
+
+        if let Some(rest) = first.text.strip_prefix('+') {
+            // `+` 可以紧贴后面的单词，如 `+IC=0`
+            if rest.is_empty() {
+                words.remove(0);
+            } else {
+                words[0].text = rest;
+                words[0].offset += 1;
+                words[0].range.start.character += 1;
+            }
+            if let Some(last) = statements.last_mut() {
+                last.words.extend(words);
+            }
+            continue;
+        }
+
+        let keyword = words[0].text.to_uppercase();
+        let name = words.get(1).map_or("", |w| w.text).to_uppercase();
+        statements.push(Statement {
+            words,
+            scope: scope.clone(),
+        });
+        match keyword.as_str() {
+            ".SUBCKT" => scope.push(name),
+            ".ENDS" => {
+                scope.pop();
+            }
+            _ => {}
+        }
+    }
+    statements
+}
+
+/// 切分一行中的单词（字节范围）；`*` 注释行为空，`;` 之后的行内注释被忽略，
+/// `,` 与空白一样只起分隔作用
+pub fn line_words(line: &str) -> Vec<(usize, usize)> {
+    if line.trim_start().starts_with('*') {
+        return vec![];
+    }
+    let code = line.split(';').next().unwrap_or_default();
+
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (i, c) in code.char_indices() {
+        let is_separator = c.is_whitespace() || matches!(c, ',' | '(' | ')' | '=');
+        match (is_separator, word_start) {
+            (true, Some(st)) => {
+                words.push((st, i));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(st) = word_start {
+        words.push((st, code.len()));
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statements_fold_continuations_and_track_scope() {
+        let source = "\
+.SUBCKT DIV IN,OUT ; pins
++ REF PARAMS: K=2
+R1 IN OUT 1k
+* comment
++ TC=0
+.ENDS
+R2 A 0 1k
+";
+        let statements = statements(source);
+        let keywords: Vec<String> = statements.iter().map(Statement::keyword).collect();
+        assert_eq!(keywords, vec![".SUBCKT", "R1", ".ENDS", "R2"]);
+
+        let pins: Vec<&str> = statements[0].subckt_pins().iter().map(|w| w.text).collect();
+        assert_eq!(pins, vec!["IN", "OUT", "REF"]);
+        assert_eq!(statements[0].subckt_pins()[2].line, 1);
+
+        assert_eq!(statements[1].texts(), vec!["R1", "IN", "OUT", "1k", "TC", "0"]);
+        assert!(statements[0].scope.is_empty());
+        assert_eq!(statements[1].scope, vec!["DIV"]);
+        assert_eq!(statements[2].scope, vec!["DIV"]);
+        assert!(statements[3].scope.is_empty());
+    }
+
+    #[test]
+    fn test_title_line_is_not_a_statement() {
+        // 标题以元件字母开头也不是元件
+        let statements = statements("R1 filter test\nR1 A 0 1k\n");
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].words[0].line, 1);
+        // 库文件以命令开头，没有标题
+        assert_eq!(super::statements(".MODEL D1N D\n").len(), 1);
+    }
+}
@@ -0,0 +1,75 @@
+use tower_lsp::lsp_types::Position;
+
+/// 行内字节偏移 → LSP 列（UTF-16 码元）；超出行尾按行尾计，落在字符中间按该字符起点计
+pub fn utf16_column(line_text: &str, byte_col: usize) -> u32 {
+    let mut byte_col = byte_col.min(line_text.len());
+    while !line_text.is_char_boundary(byte_col) {
+        byte_col -= 1;
+    }
+    line_text[..byte_col].encode_utf16().count() as u32
+}
+
+/// LSP 列（UTF-16 码元）→ 行内字节偏移；超出行尾按行尾计
+pub fn byte_column(line_text: &str, utf16_col: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line_text.char_indices() {
+        if units >= utf16_col {
+            return i;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line_text.len()
+}
+
+/// 全文字节偏移 → LSP 位置（行号 + UTF-16 列）
+///
+/// BMP 之外的字符（如 👍、国旗 🇨🇿 的每个区域指示符）占两个 UTF-16 码元，
+/// 按 `char` 计数会少算一列。
+pub fn byte_offset_to_lsp_position(source: &str, byte_offset: usize) -> Position {
+    let mut byte_offset = byte_offset.min(source.len());
+    while !source.is_char_boundary(byte_offset) {
+        byte_offset -= 1;
+    }
+    let before = &source[..byte_offset];
+    let line = before.matches('\n').count() as u32;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(line, utf16_column(&source[line_start..], byte_offset - line_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf16_column_astral() {
+        let line = "R👍 1 2 1k";
+        // 👍 为 4 字节、2 个 UTF-16 码元
+        assert_eq!(utf16_column(line, 1), 1);
+        assert_eq!(utf16_column(line, 5), 3);
+        assert_eq!(utf16_column(line, 3), 1); // 字符中间按起点计
+        assert_eq!(utf16_column(line, 100), 10);
+    }
+
+    #[test]
+    fn test_byte_column_round_trip() {
+        let line = "Q你👍 1 2 3 QMOD";
+        for byte in [0, 1, 4, 8, 9, line.len()] {
+            assert_eq!(byte_column(line, utf16_column(line, byte)), byte);
+        }
+        assert_eq!(byte_column(line, 100), line.len());
+    }
+
+    #[test]
+    fn test_byte_offset_to_lsp_position() {
+        let source = "* 🇨🇿 国旗\nV1 1 0 DC 5\nR👍 1 2 x";
+        // 第一行：🇨🇿 是两个区域指示符，共 8 字节、4 个码元
+        assert_eq!(byte_offset_to_lsp_position(source, 10), Position::new(0, 6));
+        assert_eq!(byte_offset_to_lsp_position(source, 0), Position::new(0, 0));
+
+        let third = source.rfind('R').unwrap();
+        let x = source.rfind('x').unwrap();
+        assert_eq!(byte_offset_to_lsp_position(source, third), Position::new(2, 0));
+        assert_eq!(byte_offset_to_lsp_position(source, x), Position::new(2, 8));
+        assert_eq!(byte_offset_to_lsp_position(source, source.len()), Position::new(2, 9));
+    }
+}
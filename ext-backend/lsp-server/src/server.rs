@@ -1,5 +1,5 @@
 use crate::handler;
-use crate::state::{ServerState, SharedServerState};
+use crate::state::{ClientSupport, ServerState, SharedServerState};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tower_lsp::lsp_types::*;
@@ -19,30 +19,63 @@ impl Server {
     }
 }
 
+/// 根据客户端能力生成服务端能力，客户端不支持的特性不予声明
+pub fn server_capabilities(support: &ClientSupport) -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL), // 使用全量同步
+                // 保存时只是重新发布诊断，客户端不发送 didSave 时无需声明
+                save: support.did_save.then_some(
+                    TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                        include_text: Some(true),
+                    }),
+                ),
+                ..Default::default()
+            },
+        )),
+
+        completion_provider: Some(CompletionOptions {
+            resolve_provider: Some(true),
+            trigger_characters: Some(vec![
+                ".".to_string(),
+                ":".to_string(),
+                " ".to_string(),
+            ]),
+            all_commit_characters: None,
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+            completion_item: None,
+        }),
+
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+
+        definition_provider: Some(OneOf::Left(true)),
+
+        document_formatting_provider: Some(OneOf::Left(true)),
+
+        semantic_tokens_provider: support.semantic_tokens.then(|| {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+                legend: handler::semantic_tokens::legend(),
+                range: Some(true),
+                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+            })
+        }),
+        ..Default::default()
+    }
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for Server {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let support = ClientSupport::from_capabilities(&params.capabilities);
+        let capabilities = server_capabilities(&support);
+        self.state.lock().await.client_support = support;
+
         // 告诉客户端我们支持的能力
         Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )), // 使用全量同步
-
-                completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec![
-                        ".".to_string(),
-                        ":".to_string(),
-                        " ".to_string(),
-                    ]),
-                    all_commit_characters: None,
-                    work_done_progress_options: WorkDoneProgressOptions::default(),
-                    completion_item: None,
-                }),
-                ..Default::default()
-            },
+            capabilities,
             server_info: Some(ServerInfo {
                 name: "my-lsp-server".into(),
                 version: Some("0.1.0".into()),
@@ -83,6 +116,26 @@ impl LanguageServer for Server {
         handler::diagnostics::on_did_change(&self.client, self.state.clone(), params).await;
     }
 
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("did_save: {:?}", params.text_document.uri),
+            )
+            .await;
+        handler::diagnostics::on_did_save(&self.client, self.state.clone(), params).await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("did_close: {:?}", params.text_document.uri),
+            )
+            .await;
+        handler::diagnostics::on_did_close(&self.client, self.state.clone(), params).await;
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         self.client
             .log_message(
@@ -97,4 +150,180 @@ impl LanguageServer for Server {
         //     .await;
         response
     }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("completion resolve: {}", item.label),
+            )
+            .await;
+        handler::completion::on_completion_resolve(self.state.clone(), item).await
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("hover at {:?}", params.text_document_position_params),
+            )
+            .await;
+        handler::hover::on_hover(self.state.clone(), params).await
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!(
+                    "goto definition at {:?}",
+                    params.text_document_position_params
+                ),
+            )
+            .await;
+        handler::definition::on_goto_definition(self.state.clone(), params).await
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("formatting: {:?}", params.text_document.uri),
+            )
+            .await;
+        handler::formatting::on_formatting(self.state.clone(), params).await
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!("semantic tokens full: {:?}", params.text_document.uri),
+            )
+            .await;
+        handler::semantic_tokens::on_semantic_tokens_full(self.state.clone(), params).await
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!(
+                    "semantic tokens delta: {:?} since {}",
+                    params.text_document.uri, params.previous_result_id
+                ),
+            )
+            .await;
+        handler::semantic_tokens::on_semantic_tokens_full_delta(self.state.clone(), params).await
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                &format!(
+                    "semantic tokens range: {:?} {:?}",
+                    params.text_document.uri, params.range
+                ),
+            )
+            .await;
+        handler::semantic_tokens::on_semantic_tokens_range(self.state.clone(), params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_with_snippets(snippet_support: bool) -> ClientCapabilities {
+        ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                completion: Some(CompletionClientCapabilities {
+                    completion_item: Some(CompletionItemCapability {
+                        snippet_support: Some(snippet_support),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snippet_support_negotiated() {
+        assert!(ClientSupport::from_capabilities(&client_with_snippets(true)).snippet);
+        assert!(!ClientSupport::from_capabilities(&client_with_snippets(false)).snippet);
+        assert!(!ClientSupport::from_capabilities(&ClientCapabilities::default()).snippet);
+    }
+
+    #[test]
+    fn test_semantic_tokens_advertised_with_client_support() {
+        let mut client = client_with_snippets(true);
+        if let Some(text_document) = client.text_document.as_mut() {
+            text_document.semantic_tokens = Some(SemanticTokensClientCapabilities::default());
+        }
+        let capabilities = server_capabilities(&ClientSupport::from_capabilities(&client));
+        match capabilities.semantic_tokens_provider {
+            Some(SemanticTokensServerCapabilities::SemanticTokensOptions(options)) => {
+                assert_eq!(options.legend.token_types.len(), handler::semantic_tokens::TOKEN_TYPES.len());
+                assert!(matches!(
+                    options.full,
+                    Some(SemanticTokensFullOptions::Delta { delta: Some(true) })
+                ));
+                assert_eq!(options.range, Some(true));
+            }
+            other => panic!("expected semantic tokens options, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens_omitted_without_client_support() {
+        let support = ClientSupport::from_capabilities(&client_with_snippets(true));
+        let capabilities = server_capabilities(&support);
+        assert!(capabilities.semantic_tokens_provider.is_none());
+        assert!(capabilities.completion_provider.is_some());
+    }
+
+    fn save_options(capabilities: &ServerCapabilities) -> Option<&TextDocumentSyncSaveOptions> {
+        match capabilities.text_document_sync.as_ref() {
+            Some(TextDocumentSyncCapability::Options(options)) => options.save.as_ref(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_save_notification_follows_client_support() {
+        let mut client = ClientCapabilities::default();
+        let capabilities = server_capabilities(&ClientSupport::from_capabilities(&client));
+        assert!(save_options(&capabilities).is_none());
+
+        client.text_document = Some(TextDocumentClientCapabilities {
+            synchronization: Some(TextDocumentSyncClientCapabilities {
+                did_save: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let capabilities = server_capabilities(&ClientSupport::from_capabilities(&client));
+        assert!(matches!(
+            save_options(&capabilities),
+            Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                include_text: Some(true)
+            }))
+        ));
+    }
 }
@@ -3,19 +3,73 @@ use spice_parser_core::ast::Program;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::{ClientCapabilities, MarkupKind, SemanticTokens, Url};
 
 #[derive(Default, Clone)]
 pub struct ServerState {
     pub documents: HashMap<Url, DocumentState>,
     //pub global_symbols: SymbolIndex,
+    pub client_support: ClientSupport,
+}
+
+/// initialize 时从客户端能力中协商出的特性开关
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ClientSupport {
+    /// 客户端能否处理 `InsertTextFormat::SNIPPET` 补全项
+    pub snippet: bool,
+    /// 客户端能否以 Markdown 渲染补全项文档
+    pub markdown: bool,
+    /// 客户端是否支持语义高亮
+    pub semantic_tokens: bool,
+    /// 客户端能否以 Markdown 渲染悬停内容
+    pub hover_markdown: bool,
+    /// 客户端是否会发送 `textDocument/didSave`
+    pub did_save: bool,
+}
+
+impl ClientSupport {
+    pub fn from_capabilities(capabilities: &ClientCapabilities) -> Self {
+        let completion_item = capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.completion.as_ref())
+            .and_then(|c| c.completion_item.as_ref());
+        Self {
+            snippet: completion_item
+                .and_then(|i| i.snippet_support)
+                .unwrap_or(false),
+            markdown: completion_item
+                .and_then(|i| i.documentation_format.as_ref())
+                .is_some_and(|formats| formats.contains(&MarkupKind::Markdown)),
+            semantic_tokens: capabilities
+                .text_document
+                .as_ref()
+                .is_some_and(|t| t.semantic_tokens.is_some()),
+            hover_markdown: capabilities
+                .text_document
+                .as_ref()
+                .and_then(|t| t.hover.as_ref())
+                .and_then(|h| h.content_format.as_ref())
+                .is_some_and(|formats| formats.contains(&MarkupKind::Markdown)),
+            did_save: capabilities
+                .text_document
+                .as_ref()
+                .and_then(|t| t.synchronization.as_ref())
+                .and_then(|s| s.did_save)
+                .unwrap_or(false),
+        }
+    }
 }
 
 #[derive(Default, Clone)]
 pub struct DocumentState {
     pub text: String,
+    /// 客户端的文档版本号
+    pub version: i32,
     pub ast: Option<Program>,
     pub symbols: Option<SymbolTable>, // 语义信息
+    /// 上次返回给客户端的语义 token，用于计算 delta
+    pub semantic_tokens: Option<SemanticTokens>,
 }
 
 /// 共享引用类型，保证并发安全
@@ -1,2 +1,3 @@
 pub mod symbol;
 pub mod table;
+pub mod terminal;
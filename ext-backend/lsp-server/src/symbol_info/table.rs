@@ -1,9 +1,11 @@
 use std::{
     collections::{BTreeMap, HashMap},
     hash::{Hash, Hasher},
+    ops::RangeInclusive,
 };
 
 use super::symbol::{SpiceSymbolKind, Symbol};
+use crate::netlist::statements;
 use spice_parser_core::{ast::{component::Component, Instruction, Name, Node, Program}, parse::ExposeNodes};
 use tower_lsp::lsp_types::{Position, Range, Url};
 
@@ -61,11 +63,19 @@ impl Ord for OrderedRange {
     }
 }
 
+/// 一个 `.SUBCKT` 定义占据的行（含 `.SUBCKT` 与 `.ENDS` 所在行）及其引脚
+#[derive(Debug, Clone)]
+pub struct SubcktScope {
+    pub lines: RangeInclusive<usize>,
+    pub pins: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SymbolTable {
     pub uri: Url,
     pub table: HashMap<String, Symbol>,
     pub range: BTreeMap<OrderedRange, String>,
+    pub subckts: Vec<SubcktScope>,
 }
 
 impl SymbolTable {
@@ -74,9 +84,83 @@ impl SymbolTable {
             uri,
             table: HashMap::new(),
             range: BTreeMap::new(),
+            subckts: Vec::new(),
+        }
+    }
+
+    /// 从源码中记录各子电路的行范围与引脚
+    ///
+    /// AST 暂不为命令生成符号，子电路边界直接按 `.SUBCKT`/`.ENDS` 语句扫描；
+    /// 引脚可以用 `,` 分隔，也可以写在 `+` 续行上。
+    pub fn collect_subckts(&mut self, text: &str) {
+        let mut open: Vec<(usize, Vec<String>)> = Vec::new();
+        for statement in statements(text) {
+            let line = statement.words[0].line;
+            match statement.keyword().as_str() {
+                ".SUBCKT" => {
+                    let pins = statement
+                        .subckt_pins()
+                        .iter()
+                        .map(|pin| pin.text.to_string())
+                        .collect();
+                    open.push((line, pins));
+                }
+                ".ENDS" => {
+                    if let Some((start, pins)) = open.pop() {
+                        self.subckts.push(SubcktScope {
+                            lines: start..=line,
+                            pins,
+                        });
+                    }
+                }
+                _ => {}
+            }
         }
     }
 
+    /// 包含该行的最内层子电路，顶层返回 `None`
+    pub fn subckt_at_line(&self, line: usize) -> Option<&SubcktScope> {
+        self.subckts
+            .iter()
+            .filter(|s| s.lines.contains(&line))
+            .min_by_key(|s| s.lines.end() - s.lines.start())
+    }
+
+    /// 按作用域排列的节点名：所在子电路的引脚与本地节点在前（顶层则为顶层节点），
+    /// 其余节点在后
+    pub fn node_names_in_scope(&self, line: usize) -> Vec<String> {
+        let scope = self.subckt_at_line(line).map(|s| s.lines.clone());
+
+        let mut local: Vec<String> = self
+            .range
+            .iter()
+            .filter(|(range, name)| {
+                self.table
+                    .get(*name)
+                    .is_some_and(|s| matches!(s.kind, SpiceSymbolKind::Node))
+                    && self
+                        .subckt_at_line(range.start.line as usize)
+                        .map(|s| s.lines.clone())
+                        == scope
+            })
+            .map(|(_, name)| name.clone())
+            .collect();
+        local.sort();
+
+        let pins = self
+            .subckt_at_line(line)
+            .map(|s| s.pins.clone())
+            .unwrap_or_default();
+
+        let mut names: Vec<String> = Vec::new();
+        for name in pins.into_iter().chain(local).chain(self.get_node_names()) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
     pub fn build_from_ast(uri: Url, program: Program) -> Self {
         let mut table = SymbolTable::new(uri.clone());
 
@@ -221,3 +305,69 @@ pub fn get_node_names(&self) -> Vec<String> {
     names
 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETLIST: &str = "\
+* divider
+V1 VCC 0 DC 5
+X1 VCC OUT 0 DIV
+.SUBCKT DIV IN, OUT ; REF 在续行上
++ REF
+R1 IN MID 1k
+R2 MID OUT 1k
+.ENDS
+R3 OUT 0 1k
+";
+
+    /// 按 `(行, 名称)` 登记节点出现位置，模拟 `build_from_ast` 的结果
+    fn table_with_node_uses(uses: &[(u32, &str)]) -> SymbolTable {
+        let mut table = SymbolTable::new(Url::parse("file:///div.cir").unwrap());
+        for (i, (line, name)) in uses.iter().enumerate() {
+            let range = Range {
+                start: Position::new(*line, i as u32),
+                end: Position::new(*line, i as u32 + name.len() as u32),
+            };
+            table.range.insert(range.into(), name.to_string());
+            table.table.insert(
+                name.to_string(),
+                Symbol {
+                    name: name.to_string(),
+                    range,
+                    kind: SpiceSymbolKind::Node,
+                    container: None,
+                    refcnt: 0,
+                },
+            );
+        }
+        table.collect_subckts(NETLIST);
+        table
+    }
+
+    #[test]
+    fn test_node_names_in_scope() {
+        let table = table_with_node_uses(&[
+            (1, "VCC"),
+            (2, "VCC"),
+            (2, "OUT"),
+            (5, "IN"),
+            (5, "MID"),
+            (6, "MID"),
+            (6, "OUT"),
+            (8, "OUT"),
+        ]);
+        assert_eq!(table.subckts.len(), 1);
+        assert_eq!(table.subckts[0].lines, 3..=7);
+        assert_eq!(table.subckts[0].pins, vec!["IN", "OUT", "REF"]);
+
+        // 子电路内：引脚在前，本地节点其次，其余节点最后
+        assert_eq!(
+            table.node_names_in_scope(5),
+            vec!["IN", "OUT", "REF", "MID", "VCC"]
+        );
+        // 顶层：顶层节点在前，子电路内部节点在后
+        assert_eq!(table.node_names_in_scope(8), vec!["OUT", "VCC", "IN", "MID"]);
+    }
+}
@@ -0,0 +1,55 @@
+use std::ops::Range;
+
+/// 元件各端子（节点字段）的含义，顺序与网表中节点出现的顺序一致
+///
+/// `K`（互感）引用的是电感元件而非节点，返回空；`X` 的节点个数由子电路决定，
+/// 需用 [`node_token_range`] 按整行判断。
+pub fn terminal_roles(letter: char) -> &'static [&'static str] {
+    match letter.to_ascii_uppercase() {
+        'B' | 'J' => &["drain node", "gate node", "source node"],
+        'C' | 'D' | 'F' | 'H' | 'I' | 'L' | 'R' | 'V' => &["(+) node", "(-) node"],
+        'E' | 'G' => &[
+            "(+) node",
+            "(-) node",
+            "(+) controlling node",
+            "(-) controlling node",
+        ],
+        'M' => &["drain node", "gate node", "source node", "bulk/substrate node"],
+        'Q' => &["collector node", "base node", "emitter node"],
+        'S' => &[
+            "(+) switch node",
+            "(-) switch node",
+            "(+) controlling node",
+            "(-) controlling node",
+        ],
+        'T' => &[
+            "A port (+) node",
+            "A port (-) node",
+            "B port (+) node",
+            "B port (-) node",
+        ],
+        'W' => &["(+) switch node", "(-) switch node"],
+        'Z' => &["collector node", "gate node", "emitter node"],
+        _ => &[],
+    }
+}
+
+/// 元件语句中节点 token 的下标范围（第 0 个 token 是元件名）
+///
+/// `X` 行形如 `X<name> [node]* <subcircuit name> [PARAMS: ...]`，
+/// 子电路名之前的 token 都是节点。
+pub fn node_token_range(tokens: &[&str]) -> Range<usize> {
+    let Some(letter) = tokens.first().and_then(|t| t.chars().next()) else {
+        return 0..0;
+    };
+    if letter.eq_ignore_ascii_case(&'X') {
+        let end = tokens
+            .iter()
+            .position(|t| t.ends_with(':') || t.contains('='))
+            .unwrap_or(tokens.len());
+        // end - 1 处是子电路名
+        return 1..end.saturating_sub(1).max(1);
+    }
+    let count = terminal_roles(letter).len();
+    1..(1 + count).min(tokens.len()).max(1)
+}